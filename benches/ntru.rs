@@ -0,0 +1,61 @@
+use criterion::{
+    criterion_group, criterion_main, BenchmarkId, Criterion, Throughput,
+};
+use ntru::{encparams::ALL_PARAM_SETS, rand::RNG_DEFAULT};
+
+/// Measure key generation, encryption & decryption for every parameter set
+fn bench_params(c: &mut Criterion) {
+    // A single RNG is shared by every benchmark so that its initialization
+    // isn't measured
+    let rng = ntru::rand::init(&RNG_DEFAULT).expect("failed to initialize rng");
+
+    for params in &ALL_PARAM_SETS {
+        let name = params.get_name().trim_end_matches('\0').to_owned();
+        let mut group = c.benchmark_group(&name);
+
+        group.bench_function("keygen", |b| {
+            b.iter(|| ntru::generate_key_pair(params, &rng).unwrap())
+        });
+
+        let key_pair = ntru::generate_key_pair(params, &rng).unwrap();
+
+        // Smallest & largest plaintexts accepted by the parameter set
+        for len in [1, params.max_msg_len() as usize] {
+            let plaintext = vec![0x61; len];
+            let ciphertext =
+                ntru::encrypt(&plaintext, key_pair.get_public(), params, &rng)
+                    .unwrap();
+
+            group.throughput(Throughput::Bytes(len as u64));
+            group.bench_with_input(
+                BenchmarkId::new("encrypt", len),
+                &plaintext,
+                |b, plaintext| {
+                    b.iter(|| {
+                        ntru::encrypt(
+                            plaintext,
+                            key_pair.get_public(),
+                            params,
+                            &rng,
+                        )
+                        .unwrap()
+                    })
+                },
+            );
+            group.bench_with_input(
+                BenchmarkId::new("decrypt", len),
+                &ciphertext,
+                |b, ciphertext| {
+                    b.iter(|| {
+                        ntru::decrypt(ciphertext, &key_pair, params).unwrap()
+                    })
+                },
+            );
+        }
+
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_params);
+criterion_main!(benches);
@@ -0,0 +1,53 @@
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::{encoding::Encoding, params::ParamSetId, remote};
+
+/// Defaults loaded from a config file
+///
+/// Every field is optional, explicit command line arguments always take
+/// precedence over the values found here.
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Parameter set to use
+    pub params: Option<ParamSetId>,
+
+    /// Public key file used by `enc` & `dec`
+    pub public_key: Option<PathBuf>,
+
+    /// Private key file used by `dec`
+    pub private_key: Option<PathBuf>,
+
+    /// Text encoding of the key files
+    pub encoding: Option<Encoding>,
+
+    /// Whether `enc` writes & `dec` reads ciphertexts as text, like `--armor`
+    pub armor: Option<bool>,
+}
+
+/// Read a config file, parsed as JSON if it has a `.json` extension and as
+/// TOML otherwise
+pub fn load(path: PathBuf) -> Config {
+    let content =
+        std::fs::read_to_string(&path).expect("can't read config file");
+
+    let mut config: Config = match path.extension() {
+        Some(extension) if extension == "json" => {
+            serde_json::from_str(&content).expect("invalid config file")
+        },
+        _ => toml::from_str(&content).expect("invalid config file"),
+    };
+
+    // Key paths are relative to the config file, not to the working directory
+    let base = path.parent().unwrap_or_else(|| Path::new(""));
+    let resolve = |key: PathBuf| match remote::is_url(&key) {
+        true => key,
+        false => base.join(key),
+    };
+    config.public_key = config.public_key.map(resolve);
+    config.private_key = config.private_key.map(resolve);
+
+    config
+}
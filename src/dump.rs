@@ -0,0 +1,141 @@
+use std::mem::size_of;
+
+use ntru::encparams::EncParams;
+
+/// Hash function of libntru, e.g. `ntru_sha256`
+type Hash = unsafe extern "C" fn(*const u8, u16, *mut u8);
+
+/// Hash function of libntru for several inputs, e.g. `ntru_sha256_4way`
+type HashWay = unsafe extern "C" fn(*const *const u8, u16, *mut *mut u8);
+
+/// Copy of the layout of `EncParams`, which mirrors libntru's `NtruEncParams`
+/// but keeps its fields private
+#[repr(C)]
+struct RawEncParams {
+    name: [u8; 11],
+    n: u16,
+    q: u16,
+    prod_flag: u8,
+    df1: u16,
+    df2: u16,
+    df3: u16,
+    dg: u16,
+    dm0: u16,
+    db: u16,
+    c: u16,
+    min_calls_r: u16,
+    min_calls_mask: u16,
+    hash_seed: u8,
+    oid: [u8; 3],
+    hash: Hash,
+    hash_4way: HashWay,
+    hash_8way: HashWay,
+    hlen: u16,
+    pklen: u16,
+}
+
+// Catch any layout change of `EncParams` in a new version of the bindings
+const _: () = assert!(size_of::<RawEncParams>() == size_of::<EncParams>());
+
+/// Get the fields of the libntru parameter struct
+fn raw(params: &EncParams) -> &RawEncParams {
+    // SAFETY: both structs are `repr(C)` with the same fields, in the same
+    // order, of the same sizes
+    unsafe { &*(params as *const EncParams as *const RawEncParams) }
+}
+
+/// Get the number of ones, which is also the number of negative ones, of each
+/// ternary polynomial of a private key: f1, f2 & f3 in product form, else F
+pub fn private_weights(params: &EncParams) -> Vec<u16> {
+    let raw = raw(params);
+    match raw.prod_flag {
+        0 => vec![raw.df1],
+        _ => vec![raw.df1, raw.df2, raw.df3],
+    }
+}
+
+/// Get the 3-byte object identifier of a parameter set in hexadecimal, as
+/// assigned by EESS #1 (Efficient Embedded Security Standards, v3.1) and
+/// copied by libntru
+pub fn oid(params: &EncParams) -> String {
+    raw(params)
+        .oid
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Format every field of the libntru parameter struct, one per line, for
+/// debugging only
+pub fn debug_dump(params: &EncParams) -> String {
+    let raw = raw(params);
+
+    let name = String::from_utf8_lossy(&raw.name);
+    let fields = [
+        ("name", format!("{:?}", name)),
+        ("N", raw.n.to_string()),
+        ("q", raw.q.to_string()),
+        ("prod_flag", raw.prod_flag.to_string()),
+        ("df1", raw.df1.to_string()),
+        ("df2", raw.df2.to_string()),
+        ("df3", raw.df3.to_string()),
+        ("dg", raw.dg.to_string()),
+        ("dm0", raw.dm0.to_string()),
+        ("db", raw.db.to_string()),
+        ("c", raw.c.to_string()),
+        ("min_calls_r", raw.min_calls_r.to_string()),
+        ("min_calls_mask", raw.min_calls_mask.to_string()),
+        ("hash_seed", raw.hash_seed.to_string()),
+        ("oid", oid(params)),
+        ("hash", format!("{:p}", raw.hash as *const ())),
+        ("hash_4way", format!("{:p}", raw.hash_4way as *const ())),
+        ("hash_8way", format!("{:p}", raw.hash_8way as *const ())),
+        ("hlen", raw.hlen.to_string()),
+        ("pklen", raw.pklen.to_string()),
+    ];
+
+    fields
+        .iter()
+        .map(|(field, value)| format!("{:>14} :: {}\n", field, value))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use ntru::encparams::ALL_PARAM_SETS;
+
+    use super::*;
+
+    #[test]
+    fn debug_dump_matches_raw_fields() {
+        for params in &ALL_PARAM_SETS {
+            let dump = debug_dump(params);
+            let field = |name: &str| {
+                dump.lines()
+                    .find_map(|line| {
+                        let (field, value) = line.split_once(" :: ")?;
+                        (field.trim_start() == name).then_some(value)
+                    })
+                    .unwrap_or_else(|| panic!("no {} in {}", name, dump))
+                    .to_owned()
+            };
+
+            let raw = raw(params);
+            assert_eq!(dump.lines().count(), 20);
+            assert_eq!(field("N"), raw.n.to_string());
+            assert_eq!(field("N"), params.get_n().to_string());
+            assert_eq!(field("q"), params.get_q().to_string());
+            assert_eq!(field("prod_flag"), raw.prod_flag.to_string());
+            assert_eq!(field("df1"), raw.df1.to_string());
+            assert_eq!(field("df2"), raw.df2.to_string());
+            assert_eq!(field("df3"), raw.df3.to_string());
+            assert_eq!(field("dg"), raw.dg.to_string());
+            assert_eq!(field("db"), params.get_db().to_string());
+            assert_eq!(field("c"), raw.c.to_string());
+            assert_eq!(field("oid"), oid(params));
+            assert_eq!(field("pklen"), raw.pklen.to_string());
+            assert!(field("name")
+                .contains(params.get_name().trim_end_matches('\0')));
+        }
+    }
+}
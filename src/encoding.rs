@@ -0,0 +1,353 @@
+use std::sync::OnceLock;
+
+use serde::{de, Deserialize, Deserializer};
+use zeroize::Zeroizing;
+
+/// Text encoding of the key files
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    /// Standard base64, 33% larger than the key
+    Base64,
+    /// ZeroMQ's base85 variant, 25% larger than the key
+    Z85,
+}
+
+/// Encoding selected for this run, base64 if none has been
+static SELECTED: OnceLock<Encoding> = OnceLock::new();
+
+/// Byte order mark that some editors write at the start of text files
+const BOM: &str = "\u{feff}";
+
+/// Prefix of the parameter set name in the one-line format of public keys
+const LINE_PREFIX: &str = "ntru-";
+
+/// Characters of Z85, ordered by value
+const Z85_ALPHABET: &[u8; 85] = b"0123456789abcdefghijklmnopqrstuvwxyzABCDEF\
+    GHIJKLMNOPQRSTUVWXYZ.-:+=^!/*?&<>()[]{}@%$#";
+
+/// Parse an encoding name, e.g. given on the command line
+pub fn parse(maybe_name: &str) -> Result<Encoding, String> {
+    match maybe_name.trim().to_ascii_lowercase().as_str() {
+        "base64" => Ok(Encoding::Base64),
+        "z85" => Ok(Encoding::Z85),
+        _ => Err(format!("unknown encoding: {}", maybe_name)),
+    }
+}
+
+impl<'de> Deserialize<'de> for Encoding {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        let maybe_name = String::deserialize(deserializer)?;
+        parse(&maybe_name).map_err(de::Error::custom)
+    }
+}
+
+/// Select the encoding used to read & write keys
+pub fn select(encoding: Encoding) { SELECTED.get_or_init(|| encoding); }
+
+/// Get the selected encoding
+fn selected() -> Encoding { *SELECTED.get().unwrap_or(&Encoding::Base64) }
+
+/// Encode bytes with the selected encoding
+pub fn encode(bytes: &[u8]) -> String { encode_with(selected(), bytes) }
+
+/// Encode bytes with `encoding`
+fn encode_with(encoding: Encoding, bytes: &[u8]) -> String {
+    match encoding {
+        Encoding::Base64 => base64::encode(bytes),
+        Encoding::Z85 => z85_encode(bytes),
+    }
+}
+
+/// Decode text, without surrounding whitespaces, with the selected encoding
+pub fn decode(text: &str) -> Option<Vec<u8>> { decode_with(selected(), text) }
+
+/// Decode text, without surrounding whitespaces, with `encoding`
+fn decode_with(encoding: Encoding, text: &str) -> Option<Vec<u8>> {
+    match encoding {
+        Encoding::Base64 => base64::decode(text).ok(),
+        Encoding::Z85 => z85_decode(text),
+    }
+}
+
+/// Decode secret text like `decode`, e.g. a private key, into a buffer that is
+/// never reallocated and is zeroed once dropped, even if decoding fails
+pub fn decode_secret(text: &str) -> Option<Zeroizing<Vec<u8>>> {
+    // Both decode fewer bytes than characters, base64 by chunks of 6 bytes
+    let mut bytes = Zeroizing::new(Vec::with_capacity(text.len() + 8));
+    let capacity = bytes.capacity();
+
+    let decoded = match selected() {
+        Encoding::Base64 => {
+            base64::decode_config_buf(text, base64::STANDARD, &mut bytes)
+                .is_ok()
+        },
+        Encoding::Z85 => z85_decode_into(text, &mut bytes).is_some(),
+    };
+    debug_assert_eq!(bytes.capacity(), capacity);
+
+    decoded.then_some(bytes)
+}
+
+/// Encode a ciphertext as text with the selected encoding, on one line
+pub fn armor(ciphertext: &[u8]) -> String { encode(ciphertext) + "\n" }
+
+/// Decode a ciphertext from text with the selected encoding, ignoring a
+/// leading BOM, whitespaces & line breaks, e.g. pasted from an email
+pub fn dearmor(text: &[u8]) -> Option<Vec<u8>> {
+    dearmor_with(selected(), text)
+}
+
+/// Decode a ciphertext from text with `encoding`, base64 accepting both the
+/// standard & the URL-safe alphabets
+fn dearmor_with(encoding: Encoding, text: &[u8]) -> Option<Vec<u8>> {
+    let text = text.strip_prefix(BOM.as_bytes()).unwrap_or(text);
+    let text = text.iter().filter(|c| !c.is_ascii_whitespace());
+
+    match encoding {
+        Encoding::Base64 => {
+            let text = text
+                .map(|&c| match c {
+                    b'-' => b'+',
+                    b'_' => b'/',
+                    c => c,
+                })
+                .collect::<Vec<_>>();
+            base64::decode(text).ok()
+        },
+        Encoding::Z85 => {
+            let text = String::from_utf8(text.copied().collect()).ok()?;
+            z85_decode(&text)
+        },
+    }
+}
+
+/// Split the text of a key file into its first non-empty line, the key, and
+/// the rest of the content, e.g. comments appended by other tools, ignoring a
+/// leading BOM and CRLF line endings left by text editors
+pub fn split_key(text: &str) -> (&str, &str) {
+    let text = text.trim_start_matches(BOM).trim_start();
+    let (key, rest) = text.split_once('\n').unwrap_or((text, ""));
+    (key.trim(), rest.trim())
+}
+
+/// Split a public key in the one-line format `ntru-<parameter set> <key>
+/// [comment]` into the name of its parameter set & the key, a bare key has no
+/// name
+pub fn parse_line(line: &str) -> (Option<&str>, &str) {
+    let mut tokens = line.split_whitespace();
+    match (tokens.next(), tokens.next()) {
+        (Some(name), Some(key)) if name.starts_with(LINE_PREFIX) => {
+            (name.strip_prefix(LINE_PREFIX), key)
+        },
+        _ => (None, line),
+    }
+}
+
+/// Format a public key of the parameter set `name` in the one-line format
+pub fn to_line(name: &str, public_key: &[u8], comment: &str) -> String {
+    let line = format!(
+        "{}{} {} {}",
+        LINE_PREFIX,
+        name.to_ascii_lowercase(),
+        encode(public_key),
+        comment
+    );
+    line.trim_end().to_owned()
+}
+
+/// Get the length of the text encoding at most `len` bytes
+pub fn max_encoded_len(len: u64) -> u64 {
+    match selected() {
+        Encoding::Base64 => len.saturating_add(2) / 3 * 4,
+        Encoding::Z85 => len.saturating_mul(5).saturating_add(3) / 4,
+    }
+}
+
+/// Get the maximum number of bytes decoded from a text of `len` characters
+pub fn max_decoded_len(len: u64) -> u64 {
+    match selected() {
+        Encoding::Base64 => len / 4 * 3,
+        Encoding::Z85 => len / 5 * 4 + (len % 5).saturating_sub(1),
+    }
+}
+
+/// Encode every 4 bytes as 5 characters, a final group of 1 to 3 bytes is
+/// padded with zeros and only its first 2 to 4 characters are kept
+fn z85_encode(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity((bytes.len() * 5).div_ceil(4));
+
+    for group in bytes.chunks(4) {
+        let mut padded = [0; 4];
+        padded[..group.len()].copy_from_slice(group);
+        let mut value = u32::from_be_bytes(padded);
+
+        let mut digits = [0; 5];
+        for digit in digits.iter_mut().rev() {
+            *digit = Z85_ALPHABET[(value % 85) as usize];
+            value /= 85;
+        }
+
+        text.extend(digits[..group.len() + 1].iter().map(|&c| c as char));
+    }
+
+    text
+}
+
+/// Decode every 5 characters as 4 bytes, a final group of 2 to 4 characters is
+/// padded with the highest digit and decoded as 1 to 3 bytes
+fn z85_decode(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() * 4 / 5);
+    z85_decode_into(text, &mut bytes)?;
+    Some(bytes)
+}
+
+/// Decode Z85 text like `z85_decode`, appending the bytes to `bytes`
+fn z85_decode_into(text: &str, bytes: &mut Vec<u8>) -> Option<()> {
+    if text.len() % 5 == 1 {
+        return None;
+    }
+
+    for group in text.as_bytes().chunks(5) {
+        let mut value = 0u64;
+        for i in 0..5 {
+            let digit = match group.get(i) {
+                Some(c) => Z85_ALPHABET.iter().position(|d| d == c)?,
+                None => 84,
+            };
+            value = value * 85 + digit as u64;
+        }
+
+        let value = u32::try_from(value).ok()?;
+        bytes.extend_from_slice(&value.to_be_bytes()[..group.len() - 1]);
+    }
+
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_secret_never_reallocates() {
+        let key = split_key(include_str!("../test/key/private.txt")).0;
+        let bytes = decode_secret(key).unwrap();
+        assert_eq!(*bytes, decode(key).unwrap());
+        assert_eq!(bytes.capacity(), key.len() + 8);
+
+        assert!(decode_secret("not base64!").is_none());
+    }
+
+    #[test]
+    fn split_key_keeps_the_first_line() {
+        let cases = [
+            ("key", ("key", "")),
+            ("key\n", ("key", "")),
+            ("\n\n  key  \n", ("key", "")),
+            ("key\n# comment\nmore\n", ("key", "# comment\nmore")),
+            ("key\r\n# comment\r\n", ("key", "# comment")),
+        ];
+        for (text, split) in cases {
+            assert_eq!(split_key(text), split, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn bom_and_crlf_are_ignored() {
+        let key = include_str!("../test/key/public.txt");
+        let bytes = decode_with(Encoding::Base64, split_key(key).0).unwrap();
+        let edited = format!("{}{}", BOM, key.replace('\n', "\r\n"));
+        assert_eq!(split_key(&edited), split_key(key));
+        assert_eq!(
+            decode_with(Encoding::Base64, split_key(&edited).0),
+            Some(bytes)
+        );
+
+        let armored = include_str!("../test/pinned.enc.txt");
+        let ciphertext = dearmor_with(Encoding::Base64, armored.as_bytes());
+        let edited = format!("{}{}", BOM, armored.replace('\n', "\r\n"));
+        assert!(ciphertext.is_some());
+        assert_eq!(
+            dearmor_with(Encoding::Base64, edited.as_bytes()),
+            ciphertext
+        );
+    }
+
+    #[test]
+    fn dearmor_accepts_url_safe_base64() {
+        let bytes = [0xfb, 0xff, 0xbf, 0xfe];
+        let text = base64::encode_config(bytes, base64::URL_SAFE);
+        assert_eq!(text, "-_-__g==");
+        assert_eq!(
+            dearmor_with(Encoding::Base64, text.as_bytes()),
+            Some(bytes.to_vec())
+        );
+
+        let armored = include_bytes!("../test/pinned.enc.txt");
+        let ciphertext = dearmor_with(Encoding::Base64, armored).unwrap();
+        let text = base64::encode_config(&ciphertext, base64::URL_SAFE_NO_PAD);
+        assert_eq!(
+            dearmor_with(Encoding::Base64, text.as_bytes()),
+            Some(ciphertext)
+        );
+    }
+
+    #[test]
+    fn one_line_keys_round_trip() {
+        let key = [0x01, 0x91, 0x08, 0x00, 0x2a];
+        let line = to_line("EES401EP1", &key, "alice@example.com");
+        assert_eq!(line, "ntru-ees401ep1 AZEIACo= alice@example.com");
+        assert_eq!(parse_line(&line), (Some("ees401ep1"), "AZEIACo="));
+        assert_eq!(to_line("EES401EP1", &key, ""), "ntru-ees401ep1 AZEIACo=");
+
+        // NIST names keep their own prefix, bare keys have none
+        let line = "ntru-hps2048509 AZEIACo=";
+        assert_eq!(parse_line(line), (Some("hps2048509"), "AZEIACo="));
+        assert_eq!(parse_line("AZEIACo="), (None, "AZEIACo="));
+        assert_eq!(
+            parse_line("ees401ep1 AZEIACo="),
+            (None, "ees401ep1 AZEIACo=")
+        );
+    }
+
+    #[test]
+    fn z85_matches_the_spec() {
+        let bytes = [0x86, 0x4f, 0xd2, 0x6f, 0xb5, 0x59, 0xf7, 0x5b];
+        assert_eq!(z85_encode(&bytes), "HelloWorld");
+        assert_eq!(z85_decode("HelloWorld").unwrap(), bytes);
+    }
+
+    #[test]
+    fn z85_round_trips_any_length() {
+        let bytes = (0..=255).rev().collect::<Vec<u8>>();
+        for len in 0..=9 {
+            let text = z85_encode(&bytes[..len]);
+            assert_eq!(text.len(), (len * 5).div_ceil(4));
+            assert_eq!(z85_decode(&text).unwrap(), bytes[..len]);
+        }
+        assert_eq!(z85_decode("0"), None);
+    }
+
+    #[test]
+    fn z85_round_trips_keys_and_ciphertexts() {
+        let key = include_str!("../test/key/public.txt");
+        let key = decode_with(Encoding::Base64, split_key(key).0).unwrap();
+        let text = encode_with(Encoding::Z85, &key);
+        assert_eq!(decode_with(Encoding::Z85, &text).unwrap(), key);
+
+        let armored = include_bytes!("../test/pinned.enc.txt");
+        let ciphertext = dearmor_with(Encoding::Base64, armored).unwrap();
+        let text = encode_with(Encoding::Z85, &ciphertext) + "\n";
+        assert!(text.len() < armored.len());
+
+        // Pasted text may be wrapped
+        let wrapped = text
+            .as_bytes()
+            .chunks(64)
+            .collect::<Vec<_>>()
+            .join(&b"\r\n"[..]);
+        assert_eq!(dearmor_with(Encoding::Z85, &wrapped).unwrap(), ciphertext);
+    }
+}
@@ -0,0 +1,105 @@
+use std::{fmt, sync::OnceLock};
+
+use ntru::{
+    rand::{RandContext, RNG_CTR_DRBG, RNG_DEFAULT},
+    types::Error,
+};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+/// Secret mixed into the seed of every RNG (`--extra-entropy`), if any
+static EXTRA: OnceLock<Zeroizing<Vec<u8>>> = OnceLock::new();
+
+/// Mix a secret, e.g. the output of a hardware token, into the entropy of the
+/// OS for every RNG initialized afterwards
+pub fn add(extra: Zeroizing<Vec<u8>>) { EXTRA.get_or_init(|| extra); }
+
+/// Initialize an RNG from the entropy of the OS, hashed together with the
+/// extra secret if one has been added
+///
+/// The seed is the SHA-256 of fresh OS entropy followed by the secret, so a
+/// weak or known secret never makes it weaker than the OS entropy alone.
+pub fn init() -> Result<RandContext, Error> {
+    let os = ntru::rand::init(&RNG_DEFAULT)?;
+    let Some(extra) = EXTRA.get() else {
+        return Ok(os);
+    };
+
+    let entropy = Zeroizing::new(ntru::rand::generate(32, &os)?);
+    let seed = Zeroizing::new(
+        Sha256::new()
+            .chain_update(&*entropy)
+            .chain_update(&**extra)
+            .finalize()
+            .to_vec(),
+    );
+
+    seeded(&seed)
+}
+
+/// Seed of a deterministic RNG, e.g. reported by `selftest` to replay a
+/// round-trip
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Seed(pub [u8; 32]);
+
+impl Seed {
+    /// Generate a new seed with `rng`
+    pub fn generate(rng: &RandContext) -> Result<Seed, Error> {
+        let bytes = ntru::rand::generate(32, rng)?;
+        Ok(Seed(bytes[..].try_into().expect("32 bytes were generated")))
+    }
+
+    /// Parse a seed from its 64 hexadecimal digits
+    pub fn parse(hex: &str) -> Result<Seed, String> {
+        let hex = hex.trim();
+        let mut seed = [0; 32];
+        if hex.len() != 2 * seed.len() {
+            return Err(format!("seeds are 64 hexadecimal digits: {}", hex));
+        }
+        for (byte, digits) in seed.iter_mut().zip(hex.as_bytes().chunks(2)) {
+            *byte = std::str::from_utf8(digits)
+                .ok()
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                .ok_or_else(|| format!("invalid seed: {}", hex))?;
+        }
+        Ok(Seed(seed))
+    }
+}
+
+impl fmt::Display for Seed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+    }
+}
+
+/// Initialize a deterministic RNG from `seed`
+///
+/// libntru only sets up its CTR_DRBG when initializing its default RNG, so one
+/// is initialized & dropped first, otherwise the output would depend on whether
+/// any RNG was initialized earlier in the process.
+pub fn seeded(seed: &[u8]) -> Result<RandContext, Error> {
+    ntru::rand::init(&RNG_DEFAULT)?;
+    ntru::rand::init_det(&RNG_CTR_DRBG, seed)
+}
+
+/// Initialize an RNG seeded by the SHA-256 of an exported public key & of a
+/// plaintext, so that encrypting the same plaintext to the same key always
+/// gives the same ciphertext
+///
+/// This convergent encryption reveals which ciphertexts share a plaintext, and
+/// lets anyone holding the public key confirm a guessed plaintext.
+pub fn convergent(
+    public_key: &[u8],
+    plaintext: &[u8],
+) -> Result<RandContext, Error> {
+    let seed = Zeroizing::new(
+        Sha256::new()
+            .chain_update(b"ntru convergent encryption")
+            .chain_update(public_key)
+            .chain_update(plaintext)
+            .finalize()
+            .to_vec(),
+    );
+
+    seeded(&seed)
+}
@@ -0,0 +1,124 @@
+use std::{fmt, sync::OnceLock};
+
+use ntru::{encparams::EncParams, types::PublicKey};
+use sha2::{Digest, Sha256};
+
+/// Hash function used to compute fingerprints
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    /// SHA-256, the default
+    Sha256,
+    /// BLAKE3, much faster but incompatible with the default fingerprints
+    #[cfg(feature = "blake3")]
+    Blake3,
+}
+
+/// Algorithm selected for this run, SHA-256 if none has been
+static SELECTED: OnceLock<Algorithm> = OnceLock::new();
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Algorithm::Sha256 => "sha256",
+            #[cfg(feature = "blake3")]
+            Algorithm::Blake3 => "blake3",
+        })
+    }
+}
+
+/// Parse a fingerprint algorithm name, e.g. given on the command line
+pub fn parse(maybe_name: &str) -> Result<Algorithm, String> {
+    match maybe_name.trim().to_ascii_lowercase().as_str() {
+        "sha256" => Ok(Algorithm::Sha256),
+        #[cfg(feature = "blake3")]
+        "blake3" => Ok(Algorithm::Blake3),
+        #[cfg(not(feature = "blake3"))]
+        "blake3" => {
+            Err("blake3 fingerprints require the `blake3` feature".into())
+        },
+        _ => Err(format!("unknown fingerprint algorithm: {}", maybe_name)),
+    }
+}
+
+/// Select the algorithm used to compute fingerprints
+pub fn select(algorithm: Algorithm) { SELECTED.get_or_init(|| algorithm); }
+
+/// Get the selected algorithm
+pub fn algorithm() -> Algorithm {
+    *SELECTED.get().unwrap_or(&Algorithm::Sha256)
+}
+
+/// Get the hash of an exported public key, in lowercase hexadecimal
+pub fn fingerprint(public_key: &PublicKey, params: &EncParams) -> String {
+    of_export(&public_key.export(params))
+}
+
+/// Get the fingerprint of a public key from its exported bytes
+pub fn of_export(public_key: &[u8]) -> String {
+    of_export_with(algorithm(), public_key)
+}
+
+/// Get the fingerprint of a public key from its exported bytes with
+/// `algorithm`
+fn of_export_with(algorithm: Algorithm, public_key: &[u8]) -> String {
+    let hash = match algorithm {
+        Algorithm::Sha256 => Sha256::digest(public_key).to_vec(),
+        #[cfg(feature = "blake3")]
+        Algorithm::Blake3 => blake3::hash(public_key).as_bytes().to_vec(),
+    };
+
+    hash.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Minimum number of hexadecimal characters of a fingerprint prefix, so that a
+/// typo or an empty argument can't select an unrelated key
+pub const MIN_PREFIX_LEN: usize = 8;
+
+/// Tell if `fingerprint` starts with `prefix`, a full or truncated fingerprint,
+/// in a time depending only on the length of `prefix`
+pub fn matches(fingerprint: &str, prefix: &str) -> bool {
+    if prefix.len() > fingerprint.len() {
+        return false;
+    }
+
+    let difference = fingerprint
+        .bytes()
+        .zip(prefix.bytes())
+        .fold(0, |difference, (x, y)| difference | (x ^ y));
+
+    difference == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FINGERPRINT: &str =
+        "7bd167ad18c7fc3cccd786126e6466bc1ded0e3a716119336d0a4e5521441c68";
+
+    #[test]
+    fn matches_full_fingerprints_and_prefixes() {
+        assert!(matches(FINGERPRINT, FINGERPRINT));
+        assert!(matches(FINGERPRINT, "7bd167ad"));
+        assert!(!matches(FINGERPRINT, "7bd167ae"));
+        assert!(!matches(FINGERPRINT, &format!("{}0", FINGERPRINT)));
+    }
+
+    #[test]
+    #[cfg(feature = "blake3")]
+    fn blake3_fingerprints_are_pinned() {
+        // Test vector of the BLAKE3 specification
+        assert_eq!(
+            of_export_with(Algorithm::Blake3, b""),
+            "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"
+        );
+
+        let key = include_str!("../test/key/public.txt");
+        let export = base64::decode(key.trim()).unwrap();
+        assert_eq!(of_export_with(Algorithm::Sha256, &export), FINGERPRINT);
+        assert_eq!(
+            of_export_with(Algorithm::Blake3, &export),
+            "97ccf989e22ff6c08c484c37d32046e069a19cbcc5a56cf3db565d7469f63fc9"
+        );
+    }
+}
@@ -0,0 +1,193 @@
+use std::path::{Path, PathBuf};
+
+use log::{info, log, Level};
+
+use crate::{encoding, fingerprint, params};
+
+/// Public key files found in a directory, with their fingerprints
+pub struct Keyring {
+    keys: Vec<(PathBuf, String)>,
+    ignored: Vec<(PathBuf, Level, String)>,
+}
+
+impl Keyring {
+    /// Load every public key file of a directory, spreading the work over
+    /// every CPU for large keyrings
+    ///
+    /// Files that don't contain an encoded public key of a known parameter set
+    /// (e.g. private keys) are ignored, each one is reported with the reason
+    /// with `-v`, or as a warning if it can't be read.
+    pub fn load(dir: &Path) -> Keyring {
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(dir).expect("can't read keyring") {
+            let path = entry.expect("can't read keyring").path();
+            if path.is_file() {
+                paths.push(path);
+            }
+        }
+
+        let threads =
+            std::thread::available_parallelism().map_or(1, usize::from);
+        let chunk_size = paths.len().div_ceil(threads).max(1);
+        let loaded = std::thread::scope(|scope| {
+            paths
+                .chunks(chunk_size)
+                .map(|paths| {
+                    scope.spawn(|| {
+                        paths
+                            .iter()
+                            .map(|path| load_key(path))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect::<Vec<_>>()
+                .into_iter()
+                .flat_map(|thread| thread.join().expect("can't read keyring"))
+                .collect::<Vec<_>>()
+        });
+
+        let mut keys = Vec::new();
+        let mut ignored = Vec::new();
+        for (path, fingerprint) in paths.into_iter().zip(loaded) {
+            match fingerprint {
+                Ok(fingerprint) => keys.push((path, fingerprint)),
+                Err((level, reason)) => ignored.push((path, level, reason)),
+            }
+        }
+
+        // `read_dir` doesn't guarantee any order
+        keys.sort();
+        ignored.sort();
+
+        for (path, level, reason) in &ignored {
+            log!(*level, "keyring: ignoring {}: {}", path.display(), reason);
+        }
+        info!(
+            "keyring: {} public key(s), {} other file(s) ignored",
+            keys.len(),
+            ignored.len()
+        );
+
+        Keyring { keys, ignored }
+    }
+
+    /// Find the only key file whose fingerprint starts with `prefix`, which
+    /// must be at least `MIN_PREFIX_LEN` hexadecimal characters long
+    pub fn find(&self, prefix: &str) -> &Path {
+        let prefix = prefix.trim().to_ascii_lowercase();
+        if prefix.len() < fingerprint::MIN_PREFIX_LEN
+            || !prefix.bytes().all(|c| c.is_ascii_hexdigit())
+        {
+            panic!(
+                "--recipient-fp must be at least {} hexadecimal characters, \
+                 got {:?}",
+                fingerprint::MIN_PREFIX_LEN,
+                prefix
+            );
+        }
+        let matches = self
+            .keys
+            .iter()
+            .filter(|(_, x)| fingerprint::matches(x, &prefix))
+            .collect::<Vec<_>>();
+
+        match matches[..] {
+            [] => panic!(
+                "no key matching fingerprint {} in keyring, {} other file(s) \
+                 ignored (see -v)",
+                prefix,
+                self.ignored.len()
+            ),
+            [(path, _)] => path,
+            _ => panic!(
+                "ambiguous fingerprint {}, matching keys: {}",
+                prefix,
+                matches
+                    .iter()
+                    .map(|(path, _)| path.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+/// Get the fingerprint of a public key file, or the reason why it isn't one
+/// with the level to report it at: a warning if it can't be read, as it might
+/// be the key that will be looked for
+fn load_key(path: &Path) -> Result<String, (Level, String)> {
+    let key = std::fs::read_to_string(path)
+        .map_err(|e| (Level::Warn, format!("can't read it: {}", e)))?;
+    let (_, key) = encoding::parse_line(encoding::split_key(&key).0);
+    let public_key = encoding::decode(key)
+        .ok_or_else(|| (Level::Info, "not an encoded key".to_owned()))?;
+    if params::from_public_key_len(public_key.len()).is_empty() {
+        let reason = format!(
+            "{} bytes isn't the length of a public key",
+            public_key.len()
+        );
+        return Err((Level::Info, reason));
+    }
+
+    Ok(fingerprint::of_export(&public_key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::ParamSetId;
+
+    #[test]
+    fn find_rejects_short_prefixes() {
+        let keyring = Keyring {
+            keys: vec![(PathBuf::from("a.pub"), "7bd167ad18c7fc3c".into())],
+            ignored: Vec::new(),
+        };
+        assert_eq!(keyring.find(" 7BD167AD "), Path::new("a.pub"));
+
+        for prefix in ["", " ", "7bd167a", "7bd167ad?"] {
+            let found = std::panic::catch_unwind(|| keyring.find(prefix));
+            assert!(found.is_err(), "{:?}", prefix);
+        }
+    }
+
+    #[test]
+    fn load_reports_ignored_files() {
+        let dir = std::env::temp_dir()
+            .join(format!("ntru-test-{}-keyring", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let params = ParamSetId::Ees401Ep1.params();
+        let rng = ntru::rand::init(&ntru::rand::RNG_DEFAULT).unwrap();
+        for i in (0..20).rev() {
+            let key_pair = ntru::generate_key_pair(params, &rng).unwrap();
+            let public_key = key_pair.get_public().export(params);
+            let path = dir.join(format!("{:02}.pub", i));
+            std::fs::write(path, encoding::encode(&public_key)).unwrap();
+
+            if i == 0 {
+                let private_key = key_pair.get_private().export(params);
+                let path = dir.join("00.priv");
+                std::fs::write(path, encoding::encode(&private_key)).unwrap();
+            }
+        }
+        std::fs::write(dir.join("notes.txt"), "not a key!").unwrap();
+
+        let keyring = Keyring::load(&dir);
+        let paths = keyring.keys.iter().map(|(path, _)| path.clone());
+        let expected = (0..20).map(|i| dir.join(format!("{:02}.pub", i)));
+        assert_eq!(paths.collect::<Vec<_>>(), expected.collect::<Vec<_>>());
+
+        let ignored = keyring
+            .ignored
+            .iter()
+            .map(|(path, _, reason)| (path.file_name().unwrap(), reason))
+            .collect::<Vec<_>>();
+        assert_eq!(ignored.len(), 2);
+        assert_eq!(ignored[0].0, "00.priv");
+        assert!(ignored[0].1.contains("length of a public key"));
+        assert_eq!(ignored[1].0, "notes.txt");
+        assert_eq!(ignored[1].1, "not an encoded key");
+    }
+}
@@ -0,0 +1,190 @@
+use std::{
+    io::{self, ErrorKind, Read},
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+};
+
+use zeroize::Zeroizing;
+
+use crate::encoding;
+
+/// Maximum size of an input used unless `--max-input` is given: far more than
+/// any key or ciphertext, but still safe to hold in memory
+pub const DEFAULT_MAX_INPUT: u64 = 64 * 1024 * 1024;
+
+/// Size of the buffer of outputs used unless `--io-buffer` is given
+pub const DEFAULT_IO_BUFFER: usize = 64 * 1024;
+
+/// Initial capacity of the buffers of secrets, enough for any private key
+const SECRET_CAPACITY: usize = 4096;
+
+/// Maximum size in bytes of an input once decoded
+static MAX_INPUT: AtomicU64 = AtomicU64::new(DEFAULT_MAX_INPUT);
+
+/// Set the maximum size of an input
+pub fn set_max_input(bytes: u64) { MAX_INPUT.store(bytes, Ordering::Relaxed); }
+
+/// Size in bytes of the buffer outputs are written through
+static IO_BUFFER: AtomicUsize = AtomicUsize::new(DEFAULT_IO_BUFFER);
+
+/// Set the size of the output buffer
+pub fn set_io_buffer(bytes: usize) {
+    IO_BUFFER.store(bytes, Ordering::Relaxed);
+}
+
+/// Get the size of the output buffer
+pub fn io_buffer() -> usize { IO_BUFFER.load(Ordering::Relaxed) }
+
+/// Read all the bytes of `input`, panicking as soon as there are more than the
+/// maximum input size instead of buffering them
+pub fn read_to_end(input: &mut dyn Read, what: &str) -> io::Result<Vec<u8>> {
+    let max = MAX_INPUT.load(Ordering::Relaxed);
+
+    let mut content = Vec::new();
+    input
+        .take(max.saturating_add(1))
+        .read_to_end(&mut content)?;
+    if content.len() as u64 > max {
+        too_large(what, max);
+    }
+
+    Ok(content)
+}
+
+/// Read text in the selected key encoding from `input`, panicking if it would
+/// decode to more than the maximum input size before buffering or decoding it
+/// entirely
+pub fn read_encoded(input: &mut dyn Read, what: &str) -> io::Result<String> {
+    let max = MAX_INPUT.load(Ordering::Relaxed);
+
+    // Leave room for a line ending
+    let max_encoded = encoding::max_encoded_len(max) + 2;
+
+    let mut content = String::new();
+    input
+        .take(max_encoded.saturating_add(1))
+        .read_to_string(&mut content)?;
+    if content.len() as u64 > max_encoded
+        || encoding::max_decoded_len(content.trim().len() as u64) > max
+    {
+        too_large(what, max);
+    }
+
+    Ok(content)
+}
+
+/// Read all the bytes of a secret from `input` like `read_to_end`, e.g. extra
+/// entropy, into buffers that are all zeroed once dropped
+pub fn read_secret(
+    input: &mut dyn Read,
+    what: &str,
+) -> io::Result<Zeroizing<Vec<u8>>> {
+    let max = MAX_INPUT.load(Ordering::Relaxed);
+
+    let content = read_zeroizing(input, max)?;
+    if content.len() as u64 > max {
+        too_large(what, max);
+    }
+
+    Ok(content)
+}
+
+/// Read secret text in the selected key encoding from `input` like
+/// `read_encoded`, e.g. a private key, into buffers that are all zeroed once
+/// dropped
+pub fn read_encoded_secret(
+    input: &mut dyn Read,
+    what: &str,
+) -> io::Result<Zeroizing<String>> {
+    let max = MAX_INPUT.load(Ordering::Relaxed);
+    let max_encoded = encoding::max_encoded_len(max) + 2;
+
+    let mut content = read_zeroizing(input, max_encoded)?;
+    let text = std::str::from_utf8(&content)
+        .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+    if content.len() as u64 > max_encoded
+        || encoding::max_decoded_len(text.trim().len() as u64) > max
+    {
+        too_large(what, max);
+    }
+
+    // Move the buffer into the string without copying it
+    let content = String::from_utf8(std::mem::take(&mut *content))
+        .expect("text has already been checked");
+    Ok(Zeroizing::new(content))
+}
+
+/// Read at most `max + 1` bytes from `input`, doubling the capacity of the
+/// buffer by hand, as `Vec` would leave unzeroed copies behind when growing
+fn read_zeroizing(
+    input: &mut dyn Read,
+    max: u64,
+) -> io::Result<Zeroizing<Vec<u8>>> {
+    let mut input = input.take(max.saturating_add(1));
+    let mut content = Zeroizing::new(Vec::with_capacity(SECRET_CAPACITY));
+    loop {
+        if content.len() == content.capacity() {
+            let capacity = content.capacity() * 2;
+            let mut larger = Zeroizing::new(Vec::with_capacity(capacity));
+            larger.extend_from_slice(&content);
+            content = larger;
+        }
+
+        // Read into the spare capacity, which never reallocates
+        let len = content.len();
+        let capacity = content.capacity();
+        content.resize(capacity, 0);
+        let read = input.read(&mut content[len..]);
+        content.truncate(len + *read.as_ref().unwrap_or(&0));
+
+        match read {
+            Ok(0) => return Ok(content),
+            Ok(_) => (),
+            Err(e) if e.kind() == ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Panic because `what` is over the maximum input size `max`
+fn too_large(what: &str, max: u64) -> ! {
+    panic!(
+        "{} is larger than the maximum input size of {} bytes, see \
+         --max-input",
+        what, max
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reader returning at most 7 bytes per read
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = buf.len().min(self.0.len()).min(7);
+            buf[..len].copy_from_slice(&self.0[..len]);
+            self.0 = &self.0[len..];
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn read_secret_grows_without_reallocating() {
+        let secret = (0..=255).cycle().take(3 * SECRET_CAPACITY);
+        let secret = Zeroizing::new(secret.collect::<Vec<u8>>());
+
+        let content = read_secret(&mut Trickle(&secret), "secret").unwrap();
+        assert_eq!(*content, *secret);
+        assert_eq!(content.capacity(), 4 * SECRET_CAPACITY);
+    }
+
+    #[test]
+    fn read_encoded_secret_keeps_the_text() {
+        let key = include_str!("../test/key/private.txt");
+        let content =
+            read_encoded_secret(&mut key.as_bytes(), "private key").unwrap();
+        assert_eq!(*content, key);
+    }
+}
@@ -0,0 +1,234 @@
+use std::{
+    panic,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use log::{LevelFilter, Log, Metadata, Record};
+use structopt::clap;
+
+/// Logger writing every enabled message to stderr
+struct Stderr;
+
+impl Log for Stderr {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("{}", record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: Stderr = Stderr;
+
+/// Install the logger, only reporting messages up to `level`
+pub fn init(level: LevelFilter) {
+    log::set_logger(&LOGGER).expect("failed to set logger");
+    log::set_max_level(level);
+}
+
+/// Whether only errors are reported (`--quiet`), in which case output should
+/// be kept free of any decoration
+pub fn quiet() -> bool { log::max_level() < LevelFilter::Warn }
+
+/// Exit status of the process after an unexpected error, e.g. a bug, the one
+/// of any panic
+const PANIC_CODE: i32 = 101;
+
+/// Kinds of errors with their exit status, each recognized by a part of its
+/// message, checked in order
+const KINDS: [(&str, i32, &[&str]); 5] = [
+    (
+        "usage",
+        2,
+        &[
+            "use --force",
+            "must be at least",
+            "must be between",
+            "file given",
+            "ambiguous",
+            "refusing to use it",
+            "can only wipe regular files",
+            "which is an URL",
+            "requires the `",
+        ],
+    ),
+    (
+        "io",
+        3,
+        &[
+            "can't read",
+            "can't open",
+            "can't create",
+            "can't fetch",
+            "failed to write",
+            "failed to sync",
+            "failed to rewind",
+            "failed to remove",
+        ],
+    ),
+    (
+        "input",
+        4,
+        &[
+            "invalid",
+            "public key is for",
+            "unknown parameter set",
+            "no key matching",
+            "maximum input size",
+            "plaintext too",
+        ],
+    ),
+    (
+        "crypto",
+        5,
+        &[
+            "failed to encrypt",
+            "failed to decrypt",
+            "failed to generate",
+            "rng",
+            "round-trip(s) failed",
+            "check(s) failed",
+        ],
+    ),
+    ("timeout", 6, &["timed out"]),
+];
+
+/// Whether errors are reported as JSON (`--json-errors`)
+static JSON_ERRORS: AtomicBool = AtomicBool::new(false);
+
+/// Exit with the status of the kind of error of every panic, after reporting
+/// it as usual, or as a JSON object on one line of stderr if `json` is set
+pub fn report_errors(json: bool) {
+    JSON_ERRORS.store(json, Ordering::Relaxed);
+
+    let default = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let payload = info.payload();
+        let message = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown error");
+
+        let (kind, code) = classify(message);
+        match json {
+            true => eprintln!("{}", json_error(kind, code, message)),
+            false => default(info),
+        }
+        std::process::exit(code);
+    }));
+}
+
+/// Exit right away with the status of the errors of `kind` after reporting
+/// `message`, e.g. from another thread than the stuck main one
+pub fn abort(kind: &str, message: &str) -> ! {
+    let code = KINDS
+        .iter()
+        .find(|&&(name, ..)| name == kind)
+        .map_or(PANIC_CODE, |&(_, code, _)| code);
+    match JSON_ERRORS.load(Ordering::Relaxed) {
+        true => eprintln!("{}", json_error(kind, code, message)),
+        false => eprintln!("{}", message),
+    }
+
+    std::process::exit(code)
+}
+
+/// Exit with the status of usage errors after an invalid command line, unless
+/// only help or the version has been asked for
+pub fn usage_error(error: clap::Error, json: bool) -> ! {
+    if let clap::ErrorKind::HelpDisplayed | clap::ErrorKind::VersionDisplayed =
+        error.kind
+    {
+        error.exit()
+    }
+
+    JSON_ERRORS.store(json, Ordering::Relaxed);
+    abort("usage", &error.message)
+}
+
+/// Get the kind of an error message & its exit status, `panic` if unknown
+pub fn classify(message: &str) -> (&'static str, i32) {
+    KINDS
+        .iter()
+        .find(|(_, _, parts)| parts.iter().any(|part| message.contains(part)))
+        .map_or(("panic", PANIC_CODE), |&(kind, code, _)| (kind, code))
+}
+
+/// Format an error as the JSON object of `--json-errors`
+fn json_error(kind: &str, code: i32, message: &str) -> serde_json::Value {
+    serde_json::json!({
+        "code": code,
+        "kind": kind,
+        "message": message,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_errors() {
+        let cases = [
+            (
+                "file.txt.enc already exists, use --force to overwrite it",
+                2,
+            ),
+            ("no private key file given", 2),
+            ("can't read private key file: No such file or directory", 3),
+            ("failed to write output: No space left on device", 3),
+            ("invalid private key size", 4),
+            ("invalid config file: unknown parameter set: EES1EP1", 4),
+            ("failed to decrypt: InvalidEncoding", 5),
+            ("failed to initialize rng after 5 attempt(s): Prng", 5),
+            ("timed out after 1s, see --timeout", 6),
+            ("index out of bounds", PANIC_CODE),
+        ];
+        for (message, code) in cases {
+            assert_eq!(classify(message).1, code, "{}", message);
+        }
+    }
+
+    #[test]
+    fn kinds_match_actual_messages() {
+        let sources = [
+            include_str!("main.rs"),
+            include_str!("keyring.rs"),
+            include_str!("limits.rs"),
+            include_str!("params.rs"),
+            include_str!("remote.rs"),
+        ];
+
+        // Only messages of the code count, not those of its tests
+        let sources = sources.map(|source| {
+            source.split("#[cfg(test)]").next().unwrap_or(source)
+        });
+        for (kind, _, parts) in KINDS {
+            for part in parts {
+                assert!(
+                    sources.iter().any(|source| source.contains(part)),
+                    "no {} message contains {:?}",
+                    kind,
+                    part
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn json_error_is_parseable() {
+        let (kind, code) = classify("failed to decrypt: InvalidEncoding");
+        let line = json_error(kind, code, "failed to decrypt").to_string();
+
+        let error = serde_json::from_str::<serde_json::Value>(&line).unwrap();
+        assert_eq!(error["code"], 5);
+        assert_eq!(error["kind"], "crypto");
+        assert_eq!(error["message"], "failed to decrypt");
+    }
+}
@@ -1,207 +1,2973 @@
-use std::path::PathBuf;
+mod config;
+mod dump;
+mod encoding;
+mod entropy;
+mod fingerprint;
+mod keyring;
+mod limits;
+mod logger;
+mod params;
+mod remote;
+mod timing;
 
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, IsTerminal, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::{info, warn, LevelFilter};
 use ntru::{
-    encparams::DEFAULT_PARAMS_256_BITS,
-    rand::{RandContext, RNG_DEFAULT},
-    types::{KeyPair, PrivateKey, PublicKey},
+    encparams::EncParams,
+    rand::RandContext,
+    types::{Error, KeyPair, PrivateKey, PublicKey},
 };
+use serde::Serialize;
 use structopt::StructOpt;
+use zeroize::Zeroizing;
+
+use crate::{
+    config::Config, encoding::Encoding, fingerprint::fingerprint,
+    keyring::Keyring, params::ParamSetId,
+};
 
 /// Options accepted by the CLI
 #[derive(StructOpt)]
 #[structopt(author, about)]
-enum Opt {
+struct Opt {
+    /// Config file providing defaults for the other options, parsed as JSON
+    /// if it has a `.json` extension and as TOML otherwise
+    #[structopt(long, global = true, parse(from_os_str))]
+    config: Option<PathBuf>,
+
+    /// Name of the NTRU parameter set to use (default: EES1171EP1)
+    #[structopt(
+        long,
+        global = true,
+        env = "NTRU_PARAMS",
+        parse(try_from_str = ParamSetId::try_from)
+    )]
+    params: Option<ParamSetId>,
+
+    /// Text encoding of the key files, `base64` or `z85` (default: base64)
+    #[structopt(long, global = true, parse(try_from_str = encoding::parse))]
+    encoding: Option<Encoding>,
+
+    /// Hash function of the key fingerprints, `sha256` or `blake3` if built
+    /// with the `blake3` feature (default: sha256)
+    #[structopt(long, global = true, parse(try_from_str = fingerprint::parse))]
+    fingerprint: Option<fingerprint::Algorithm>,
+
+    /// Refuse keys, plaintexts & ciphertexts larger than this many bytes
+    /// (default: 64 MiB)
+    #[structopt(long, global = true)]
+    max_input: Option<u64>,
+
+    /// File whose content, e.g. the output of a hardware token, is mixed into
+    /// the entropy of the OS seeding every RNG
+    #[structopt(long, global = true, parse(from_os_str))]
+    extra_entropy: Option<PathBuf>,
+
+    /// Abort with an error if the command takes more than this many seconds,
+    /// removing or truncating back any partially written output file, stdout
+    /// and pipes excepted
+    #[structopt(long, global = true)]
+    timeout: Option<u64>,
+
+    /// Report errors to stderr as a JSON object with `code`, `kind` and
+    /// `message` fields
+    #[structopt(long, global = true)]
+    json_errors: bool,
+
+    /// Size in bytes of the buffer outputs are written through (default:
+    /// 64 KiB)
+    #[structopt(long, global = true)]
+    io_buffer: Option<usize>,
+
+    /// Print the time spent reading, doing crypto & writing to stderr
+    #[structopt(long, global = true)]
+    time: bool,
+
+    /// Only report errors & print keys without decoration
+    #[structopt(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Report progress, timings & key fingerprints to stderr
+    #[structopt(short, long, global = true)]
+    verbose: bool,
+
+    #[structopt(subcommand)]
+    cmd: Command,
+}
+
+/// Commands accepted by the CLI
+#[derive(StructOpt)]
+enum Command {
     /// Generate key pair
     Gen {
         /// Generate public key using private key file (optional)
+        #[structopt(conflicts_with = "count")]
         private_key: Option<PathBuf>,
+
+        /// Number of key pairs to generate into `--out-dir`
+        #[structopt(long, requires = "out-dir")]
+        count: Option<usize>,
+
+        /// Directory receiving the `key_<index>.pub` & `key_<index>.priv`
+        /// files generated by `--count`
+        #[structopt(long, parse(from_os_str), requires = "count")]
+        out_dir: Option<PathBuf>,
+
+        /// Overwrite existing key files in `--out-dir`
+        #[structopt(long, requires = "count")]
+        force: bool,
     },
 
     /// Encrypt data using the public key
     Enc {
-        /// File to encrypt
+        /// File to encrypt into `<file>.enc`, or `-` to encrypt stdin to
+        /// stdout
         #[structopt(parse(from_os_str))]
         file: PathBuf,
 
-        /// Public key file in base64
-        public_key: PathBuf,
+        /// Replace the content of the file instead
+        #[structopt(long)]
+        in_place: bool,
+
+        /// Overwrite `<file>.enc` if it already exists
+        #[structopt(long, conflicts_with = "in-place")]
+        force: bool,
+
+        /// Write the ciphertext as text in the key encoding, e.g. to paste it
+        /// in an email
+        #[structopt(long)]
+        armor: bool,
+
+        /// Write the ciphertext to this inherited file descriptor instead,
+        /// without closing it (Unix only)
+        #[structopt(long, conflicts_with_all = &["in-place", "force"])]
+        output_fd: Option<i32>,
+
+        /// Split the plaintext into chunks of at most this many bytes, up to
+        /// the maximum plaintext length, each encrypted into its own record
+        #[structopt(long)]
+        chunk_size: Option<usize>,
+
+        /// Derive the randomness from the public key & the plaintext, so that
+        /// the same plaintext always gives the same ciphertext (e.g. for
+        /// deduplication), which reveals when two ciphertexts are equal and
+        /// lets anyone confirm a guess of the plaintext
+        #[structopt(long)]
+        deterministic: bool,
+
+        /// Public key file (optional if set in the config file)
+        #[structopt(conflicts_with = "recipient-fp")]
+        public_key: Option<PathBuf>,
+
+        /// Encrypt to this encoded public key instead of a public key file,
+        /// beware that it is visible in process listings & shell history
+        #[structopt(long, conflicts_with_all = &["public-key", "recipient-fp"])]
+        recipient_key: Option<String>,
+
+        /// Encrypt to the key of `--keyring` with this fingerprint (or a
+        /// unique prefix of it, at least 8 characters long) instead of a
+        /// public key file
+        #[structopt(long, requires = "keyring")]
+        recipient_fp: Option<String>,
+
+        /// Directory of public key files searched by `--recipient-fp`
+        #[structopt(long, parse(from_os_str), requires = "recipient-fp")]
+        keyring: Option<PathBuf>,
     },
 
     /// Decrypt data using the private & public key
     Dec {
-        /// File to decrypt
+        /// File to decrypt into the same path without its `.enc` extension,
+        /// or with a `.dec` one if it has none, or `-` to decrypt stdin to
+        /// stdout
         #[structopt(parse(from_os_str))]
         file: PathBuf,
 
-        /// Private key file in base64
-        private_key: PathBuf,
+        /// Replace the content of the file instead
+        #[structopt(long)]
+        in_place: bool,
 
-        /// Public key file that the ciphertext has been encrypted with in
-        /// base64
-        public_key: PathBuf,
+        /// Overwrite the decrypted file if it already exists
+        #[structopt(long, conflicts_with = "in-place")]
+        force: bool,
+
+        /// Write the plaintext to stdout instead, leaving the file untouched
+        #[structopt(long, conflicts_with_all = &["in-place", "force"])]
+        print: bool,
+
+        /// Read the ciphertext as text in the key encoding, base64 with the
+        /// standard or URL-safe alphabet, ignoring whitespaces & line breaks
+        #[structopt(long)]
+        armor: bool,
+
+        /// Write the plaintext to this inherited file descriptor instead,
+        /// without closing it (Unix only)
+        #[structopt(long, conflicts_with_all = &["in-place", "force", "print"])]
+        output_fd: Option<i32>,
+
+        /// Private key file (optional if set in the config file)
+        private_key: Option<PathBuf>,
+
+        /// Public key file that the ciphertext has been encrypted with
+        /// (optional if set in the config file)
+        public_key: Option<PathBuf>,
+    },
+
+    /// Re-encrypt a ciphertext file for another public key & parameter set
+    ///
+    /// The file is fully decrypted then encrypted again, as NTRU ciphertexts
+    /// can't be converted from a parameter set to another.
+    Migrate {
+        /// Ciphertext file to migrate, or `-` for stdin
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+
+        /// Private key file that the ciphertext can be decrypted with
+        #[structopt(parse(from_os_str))]
+        old_private_key: PathBuf,
+
+        /// Public key file that the ciphertext has been encrypted with
+        #[structopt(parse(from_os_str))]
+        old_public_key: PathBuf,
+
+        /// Public key file to encrypt the plaintext with
+        #[structopt(parse(from_os_str))]
+        new_public_key: PathBuf,
+
+        /// Parameter set of the old keys (default: `--params` or inferred
+        /// from the old public key)
+        #[structopt(long, parse(try_from_str = ParamSetId::try_from))]
+        old_params: Option<ParamSetId>,
+
+        /// Parameter set of the new public key (default: inferred from it)
+        #[structopt(long, parse(try_from_str = ParamSetId::try_from))]
+        new_params: Option<ParamSetId>,
+
+        /// File receiving the new ciphertext, which may be the migrated file,
+        /// or `-` for stdout
+        #[structopt(short, long, parse(from_os_str))]
+        output: PathBuf,
+    },
+
+    /// Overwrite a file (e.g. an old private key) and delete it
+    ///
+    /// The file is overwritten with random data then with zeros before being
+    /// removed. This is best-effort only: SSDs, copy-on-write & journaling
+    /// filesystems, snapshots and backups may keep copies of the old content.
+    Wipe {
+        /// File to wipe
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
     },
 
     /// Print general information about the NTRU used here
-    Info,
+    Info {
+        /// Print it as canonical `key=value` lines, for scripts & snapshots
+        #[structopt(long)]
+        stable: bool,
+    },
+
+    /// List the parameter sets that can be passed to `--params`
+    Params {
+        /// Print them as JSON
+        #[structopt(long)]
+        json: bool,
+
+        /// Order them by security level, then by public key length
+        #[structopt(long)]
+        by_security: bool,
+    },
+
+    /// Print version information about this build
+    Version {
+        /// Print it as JSON
+        #[structopt(long)]
+        json: bool,
+    },
+
+    /// Print a public key on one line as `ntru-<parameter set> <key>
+    /// [comment]`, e.g. for authorized-keys-like files, which is accepted
+    /// wherever a public key file is
+    OneLine {
+        /// Public key file
+        #[structopt(parse(from_os_str))]
+        public_key: PathBuf,
+
+        /// Comment appended to the line, e.g. `alice@laptop`
+        comment: Vec<String>,
+    },
+
+    /// Check that the RNG works & that a message round-trips with the
+    /// selected parameter set, e.g. after installing
+    Doctor,
+
+    /// Encrypt & decrypt random messages with a fresh key pair, reporting the
+    /// seeds reproducing any that fails, e.g. before deploying `--params`
+    Selftest {
+        /// Number of messages to encrypt & decrypt
+        #[structopt(long, default_value = "100")]
+        count: u32,
+
+        /// Seed of the key pair, as reported by a failure, to replay it
+        #[structopt(long, parse(try_from_str = entropy::Seed::parse))]
+        key_seed: Option<entropy::Seed>,
+
+        /// Seed of the message, as reported by a failure, to only replay its
+        /// round-trip instead of `--count` random ones
+        #[structopt(
+            long,
+            requires = "key-seed",
+            parse(try_from_str = entropy::Seed::parse)
+        )]
+        message_seed: Option<entropy::Seed>,
+    },
+
+    /// Measure how often random messages fail to round-trip, for research
+    #[structopt(setting = structopt::clap::AppSettings::Hidden)]
+    FailureRate {
+        /// Number of messages to encrypt & decrypt
+        #[structopt(long, default_value = "1000")]
+        trials: u32,
+    },
+
+    /// Print every field of the libntru parameter struct, for debugging
+    #[structopt(setting = structopt::clap::AppSettings::Hidden)]
+    DumpParams {
+        /// Name of the parameter set (default: the selected one)
+        #[structopt(parse(try_from_str = ParamSetId::try_from))]
+        name: Option<ParamSetId>,
+    },
+}
+
+/// NTRUEncrypt implementation doing the actual work
+const BACKEND: &str = "libntru 0.5 (https://github.com/tbuktu/libntru)";
+
+/// Optional cargo features enabled in this build
+const FEATURES: &[&str] = &[
+    #[cfg(feature = "blake3")]
+    "blake3",
+    #[cfg(feature = "http")]
+    "http",
+];
+
+/// Number of key generation attempts before giving up on RNG failures
+const KEYGEN_ATTEMPTS: usize = 3;
+
+/// Number of RNG initialization attempts before giving up, e.g. while the OS
+/// entropy source isn't ready yet early at boot
+const RNG_INIT_ATTEMPTS: usize = 5;
+
+/// Delay before retrying to initialize the RNG, doubled after every attempt
+const RNG_INIT_BACKOFF: Duration = Duration::from_millis(50);
+
+/// Outputs being written, undone if the command times out
+static PARTIAL_OUTPUTS: Mutex<Vec<Partial>> = Mutex::new(Vec::new());
+
+/// Output being written, see `--timeout`
+enum Partial {
+    /// File created by the command, or being wiped, removed
+    Remove(PathBuf),
+    /// Regular file inherited as `path`, truncated back to its length `len`
+    /// before writing
+    Truncate { path: PathBuf, file: File, len: u64 },
+}
+
+impl Partial {
+    /// Get the path the output is written to
+    fn path(&self) -> &Path {
+        match self {
+            Partial::Remove(path) | Partial::Truncate { path, .. } => path,
+        }
+    }
+
+    /// Undo the output, ignoring failures as nothing more can be done
+    fn undo(&self) {
+        let _ = match self {
+            Partial::Remove(path) => std::fs::remove_file(path),
+            Partial::Truncate { file, len, .. } => file.set_len(*len),
+        };
+    }
 }
 
 /// Get default RNG
-fn get_rng() -> RandContext {
-    ntru::rand::init(&RNG_DEFAULT).expect("failed to initialize rng")
+fn get_rng() -> RandContext { init_rng(entropy::init) }
+
+/// Initialize an RNG, retrying with an exponential backoff if it fails
+fn init_rng<T>(init: impl Fn() -> Result<T, Error>) -> T {
+    let mut attempt = 1;
+    let mut delay = RNG_INIT_BACKOFF;
+    loop {
+        match init() {
+            Ok(rng) => return rng,
+            Err(e) if attempt < RNG_INIT_ATTEMPTS => {
+                warn!("failed to initialize rng, retrying: {}", e);
+
+                // Jitter, taken from the clock as no RNG is available
+                let nanos = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |now| now.subsec_nanos());
+                std::thread::sleep(delay + delay * (nanos % 100) / 100);
+
+                attempt += 1;
+                delay *= 2;
+            },
+            Err(e) => panic!(
+                "failed to initialize rng after {} attempt(s): {}",
+                attempt, e
+            ),
+        }
+    }
+}
+
+/// Generate a key with a fresh RNG, retrying if the RNG fails transiently
+///
+/// libntru already retries internally until it finds an invertible private
+/// polynomial, any other error is permanent and isn't retried.
+fn generate<T>(what: &str, f: impl Fn(&RandContext) -> Result<T, Error>) -> T {
+    let mut attempt = 1;
+    loop {
+        match f(&get_rng()) {
+            Ok(key) => return key,
+            Err(Error::Prng) if attempt < KEYGEN_ATTEMPTS => attempt += 1,
+            Err(e) => panic!(
+                "failed to generate {} after {} attempt(s): {}",
+                what, attempt, e
+            ),
+        }
+    }
+}
+
+/// Open a file for reading, panicking with `msg` on failure
+fn open(path: &Path, msg: &str) -> File { File::open(path).expect(msg) }
+
+/// Open a key or input file, or fetch it if it's an URL
+fn open_input(path: &Path, msg: &str) -> Box<dyn Read> {
+    match remote::is_url(path) {
+        true => remote::fetch(path, msg),
+        false => Box::new(open(path, msg)),
+    }
+}
+
+/// Create a new file only readable & writable by its owner, e.g. for a private
+/// key, failing if it already exists
+fn create_private(path: &Path) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)
+}
+
+/// Remove a file if it exists
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Lock the partial outputs, even if a panicking thread poisoned them
+fn partial_outputs() -> MutexGuard<'static, Vec<Partial>> {
+    PARTIAL_OUTPUTS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Create a new output file with `create`, tracked until `complete_output`
+///
+/// The lock is held while creating the file, so the watchdog can neither miss
+/// it nor remove a file existing before, e.g. if creation fails.
+fn create_output(
+    path: &Path,
+    create: impl FnOnce(&Path) -> io::Result<File>,
+) -> io::Result<File> {
+    let mut partials = partial_outputs();
+    let file = create(path)?;
+    partials.push(Partial::Remove(path.to_owned()));
+    Ok(file)
+}
+
+/// Stop tracking the output written to `path` once it's complete
+fn complete_output(path: &Path) {
+    partial_outputs().retain(|partial| partial.path() != path);
 }
 
-/// Extract public key from file containing base64 string
-fn read_public_key(maybe_key: PathBuf) -> PublicKey {
-    let maybe_key = std::fs::read_to_string(maybe_key)
-        .expect("can't read private key file");
+/// Write `content` into a temporary file then rename it to `path`, so that
+/// `path` is never left half-written
+///
+/// The file keeps the permissions of the file it replaces, or else takes those
+/// of `input`, or else is only readable by its owner.
+fn write_atomically(
+    path: &Path,
+    content: &[u8],
+    input: &Path,
+) -> io::Result<()> {
+    let name = path.file_name().expect("not a file").to_string_lossy();
+    let tmp = path.with_file_name(format!(".{}.tmp", name));
 
-    // Remove whitespaces from key and decode base64
-    let public_key =
-        base64::decode(maybe_key.trim()).expect("invalid public key");
+    let result = (|| {
+        // A temporary file left by a killed run would make creation fail
+        remove_if_exists(&tmp)?;
+        let output = create_output(&tmp, create_private)?;
+
+        // Restrict the permissions before writing anything
+        let metadata =
+            std::fs::metadata(path).or_else(|_| std::fs::metadata(input));
+        if let Ok(metadata) = metadata {
+            output.set_permissions(metadata.permissions())?;
+        }
+
+        let mut output = BufWriter::with_capacity(limits::io_buffer(), output);
+        output.write_all(content)?;
+        let output = output.into_inner().map_err(|e| e.into_error())?;
+
+        output.sync_all()?;
+        std::fs::rename(&tmp, path)
+    })();
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    complete_output(&tmp);
+
+    result
+}
+
+/// Abort the process if it's still running after `timeout`, undoing any
+/// partially written output first
+///
+/// Output already written to stdout or to a pipe can't be taken back.
+fn start_watchdog(timeout: Duration) {
+    std::thread::spawn(move || {
+        std::thread::sleep(timeout);
+
+        // Keep the lock until exiting so that no new output gets created
+        let mut partials = partial_outputs();
+        for partial in partials.drain(..) {
+            partial.undo();
+        }
+        logger::abort(
+            "timeout",
+            &format!("timed out after {:?}, see --timeout", timeout),
+        );
+    });
+}
+
+/// Get the key of the text of a key file, ignoring the lines following it
+fn key_text<'a>(text: &'a str, what: &str) -> &'a str {
+    let (key, rest) = encoding::split_key(text);
+    if !rest.is_empty() {
+        info!(
+            "ignoring {} line(s) after the {}",
+            rest.lines().count(),
+            what
+        );
+    }
+
+    key
+}
+
+/// Extract public key from a reader containing its encoded form
+fn read_public_key(maybe_key: &mut dyn Read, params: &EncParams) -> PublicKey {
+    let key = timing::measure("read", || {
+        limits::read_encoded(maybe_key, "public key")
+    })
+    .expect("can't read public key file");
+
+    // Remove whitespaces & trailing content from key and decode it
+    let (name, key) = encoding::parse_line(key_text(&key, "public key"));
+    if let Some(name) = name {
+        match ParamSetId::from_name(name) {
+            Some(id) if id.params() == params => (),
+            _ => panic!(
+                "public key is for {}, not {}",
+                name,
+                params::name(params)
+            ),
+        }
+    }
+    let public_key = encoding::decode(key).expect("invalid public key");
 
     // Validate key size
-    if public_key.len() != DEFAULT_PARAMS_256_BITS.public_len() as usize {
+    if public_key.len() != params.public_len() as usize {
         panic!("invalid public key size");
     }
 
     PublicKey::import(&public_key)
 }
 
-/// Extract private key from file containing base64 string
-fn read_private_key(maybe_key: PathBuf) -> PrivateKey {
-    let maybe_key = std::fs::read_to_string(maybe_key)
-        .expect("can't read private key file");
+/// Guess the parameter set of a public key from its size, falling back to the
+/// default parameter set if none of them match
+fn infer_params(maybe_key: &mut dyn Read) -> &'static EncParams {
+    let maybe_key = limits::read_encoded(maybe_key, "public key")
+        .expect("can't read public key file");
+    let (name, key) = encoding::parse_line(encoding::split_key(&maybe_key).0);
+
+    // The one-line format names the parameter set
+    if let Some(name) = name {
+        return ParamSetId::from_name(name)
+            .unwrap_or_else(|| panic!("unknown parameter set: {}", name))
+            .params();
+    }
+
+    let public_key = encoding::decode(key).expect("invalid public key");
+    match params::from_public_key_len(public_key.len())[..] {
+        [] => ParamSetId::DEFAULT.params(),
+        [params] => params,
+        ref candidates => panic!(
+            "ambiguous public key size, select one of {} with --params",
+            candidates
+                .iter()
+                .map(|&params| params::name(params))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// Extract private key from a reader containing its encoded form
+fn read_private_key(
+    maybe_key: &mut dyn Read,
+    params: &EncParams,
+) -> PrivateKey {
+    // Secret buffers are zeroed once dropped
+    let key = timing::measure("read", || {
+        limits::read_encoded_secret(maybe_key, "private key")
+    })
+    .expect("can't read private key file");
 
-    // Remove whitespaces from key and decode base64
-    let private_key =
-        base64::decode(maybe_key.trim()).expect("invalid private key");
+    // Remove whitespaces & trailing content from key and decode it
+    let private_key = encoding::decode_secret(key_text(&key, "private key"))
+        .expect("invalid private key");
 
     // Validate key size
-    if private_key.len() != DEFAULT_PARAMS_256_BITS.private_len() as usize {
+    if private_key.len() != params.private_len() as usize {
         panic!("invalid private key size");
     }
 
+    // Importing trusts the weights & indices stored in the key, check them
+    // first
+    if private_key_weights(&private_key) != Some(dump::private_weights(params))
+    {
+        panic!("invalid private key: unexpected polynomial weights or indices");
+    }
+
     PrivateKey::import(&private_key)
 }
 
-/// Print a public key generated using a private key and default parameters
-fn generate_key_pair_from_private_key(private_key: PathBuf) {
-    let private_key = read_private_key(private_key);
+/// Get the number of ones of each polynomial of an exported private key, if
+/// they all have as many negative ones, at distinct indices below N
+fn private_key_weights(private_key: &[u8]) -> Option<Vec<u16>> {
+    let n = u16::from_be_bytes([*private_key.first()?, *private_key.get(1)?]);
+    let bits_per_index =
+        (u16::BITS - n.checked_sub(1)?.leading_zeros()) as usize;
+    let polynomials = match private_key.get(4)? & 4 {
+        0 => 1,
+        _ => 3,
+    };
 
-    // Generate public key from private key using default parameters
-    let public_key = ntru::generate_public(
-        &DEFAULT_PARAMS_256_BITS,
-        &private_key,
-        &get_rng(),
-    )
-    .expect("failed to generate public key");
+    // Each polynomial is stored as its number of ones & of negative ones,
+    // then the indices of these coefficients, packed from the least
+    // significant bit of each byte
+    let mut weights = Vec::with_capacity(polynomials);
+    let mut rest = private_key.get(5..)?;
+    for _ in 0..polynomials {
+        let ones = u16::from_be_bytes([*rest.first()?, *rest.get(1)?]);
+        let neg_ones = u16::from_be_bytes([*rest.get(2)?, *rest.get(3)?]);
+        if ones != neg_ones {
+            return None;
+        }
 
-    // Convert to raw bytes
-    let public_key = public_key.export(&DEFAULT_PARAMS_256_BITS);
+        let count = 2 * ones as usize;
+        let len = (bits_per_index * count).div_ceil(8);
+        let packed = rest.get(4..4 + len)?;
+
+        // The indices are secret too
+        let mut used = Zeroizing::new(vec![0u8; n as usize]);
+        for i in 0..count {
+            let start = i * bits_per_index;
+            let index = (0..bits_per_index).fold(0, |index, bit| {
+                let position = start + bit;
+                let value = (packed[position / 8] >> (position % 8)) & 1;
+                index | (value as usize) << bit
+            });
+            if index >= n as usize || used[index] != 0 {
+                return None;
+            }
+            used[index] = 1;
+        }
 
-    // Print the public key in base64
-    println!("----------------- Public Key ------------------");
-    println!("{}", base64::encode(public_key));
+        rest = &rest[4 + len..];
+        weights.push(ones);
+    }
+
+    Some(weights)
 }
 
-/// Print a private and public key pair generated using default parameters
-fn generate_key_pair() {
-    // Generate keys using default parameters
-    let key_pair =
-        ntru::generate_key_pair(&DEFAULT_PARAMS_256_BITS, &get_rng())
-            .expect("failed to generate key pair");
+/// Encode a private key, zeroing the secret buffers once dropped
+fn encode_private_key(
+    private_key: &PrivateKey,
+    params: &EncParams,
+) -> Zeroizing<String> {
+    let private_key = Zeroizing::new(private_key.export(params));
+    Zeroizing::new(encoding::encode(&private_key))
+}
+
+/// Check that a private key imported from a possibly corrupt file belongs to
+/// `params` and can be used to derive a public key
+fn validate_private_key(private_key: &PrivateKey, params: &EncParams) {
+    let n = private_key
+        .get_params()
+        .expect("invalid private key")
+        .get_n();
+    if n != params.get_n() || private_key.get_q() != params.get_q() {
+        panic!(
+            "invalid private key: not generated with {}",
+            params::name(params)
+        );
+    }
+
+    // The public key is derived from the inverse of the private polynomial
+    let (_, invertible) = private_key.get_t().invert(params.get_q() - 1);
+    if !invertible {
+        panic!("invalid private key: polynomial isn't invertible mod q");
+    }
+}
+
+/// Write a public key generated using a private key
+fn generate_key_pair_from_private_key(
+    private_key: &mut dyn Read,
+    output: &mut dyn Write,
+    params: &EncParams,
+) -> io::Result<()> {
+    let private_key = read_private_key(private_key, params);
+    validate_private_key(&private_key, params);
+
+    // Generate public key from private key
+    let public_key = timing::measure("crypto", || {
+        generate("public key", |rng| {
+            ntru::generate_public(params, &private_key, rng)
+        })
+    });
+
+    info!(
+        "public key {} fingerprint: {}",
+        fingerprint::algorithm(),
+        fingerprint(&public_key, params)
+    );
 
     // Convert to raw bytes
-    let public_key = key_pair.get_public().export(&DEFAULT_PARAMS_256_BITS);
-    let private_key = key_pair.get_private().export(&DEFAULT_PARAMS_256_BITS);
+    let public_key = public_key.export(params);
+
+    // Write the encoded public key
+    timing::measure("write", || {
+        if !logger::quiet() {
+            writeln!(
+                output,
+                "----------------- Public Key ------------------"
+            )?;
+        }
+        writeln!(output, "{}", encoding::encode(&public_key))
+    })
+}
+
+/// Write a freshly generated private and public key pair
+fn generate_key_pair(
+    output: &mut dyn Write,
+    params: &EncParams,
+) -> io::Result<()> {
+    let key_pair = timing::measure("crypto", || {
+        generate("key pair", |rng| ntru::generate_key_pair(params, rng))
+    });
+
+    info!(
+        "public key {} fingerprint: {}",
+        fingerprint::algorithm(),
+        fingerprint(key_pair.get_public(), params)
+    );
+
+    let public_key = encoding::encode(&key_pair.get_public().export(params));
+    let private_key = encode_private_key(key_pair.get_private(), params);
+
+    // Write the encoded keys, one per line without decoration if quiet
+    timing::measure("write", || {
+        if logger::quiet() {
+            writeln!(output, "{}", public_key)?;
+            return writeln!(output, "{}", *private_key);
+        }
+
+        writeln!(output, "----------------- Public Key ------------------")?;
+        writeln!(output, "{}", public_key)?;
+
+        writeln!(output)?;
 
-    // Print the keys in base64
+        writeln!(output, "----------------- Private Key -----------------")?;
+        writeln!(output, "{}", *private_key)
+    })
+}
+
+/// Generate `count` key pairs into `out_dir`, as `key_0001.pub`,
+/// `key_0001.priv`, `key_0002.pub`...
+fn generate_key_pairs(
+    count: usize,
+    out_dir: &Path,
+    force: bool,
+    params: &EncParams,
+) {
+    if count == 0 {
+        panic!("--count must be at least 1");
+    }
+
+    std::fs::create_dir_all(out_dir).expect("can't create output directory");
+
+    // Pad indices so that files are listed in order
+    let width = count.to_string().len().max(4);
+    let paths = (1..=count)
+        .map(|i| {
+            let stem = format!("key_{:0width$}", i, width = width);
+            let public_key = out_dir.join(format!("{}.pub", stem));
+            let private_key = out_dir.join(format!("{}.priv", stem));
+            (public_key, private_key)
+        })
+        .collect::<Vec<_>>();
+
+    // Check every file before generating anything to never stop halfway
+    if !force {
+        for path in paths.iter().flat_map(|(public, private)| [public, private])
+        {
+            if path.exists() {
+                panic!(
+                    "{} already exists, use --force to overwrite it",
+                    path.display()
+                );
+            }
+        }
+    }
+
+    for (public_key, private_key) in &paths {
+        let key_pair = timing::measure("crypto", || {
+            generate("key pair", |rng| ntru::generate_key_pair(params, rng))
+        });
 
-    println!("----------------- Public Key ------------------");
-    println!("{}", base64::encode(public_key));
+        info!(
+            "{}: {}",
+            public_key.display(),
+            fingerprint(key_pair.get_public(), params)
+        );
 
-    println!();
+        let public = encoding::encode(&key_pair.get_public().export(params));
+        let private = encode_private_key(key_pair.get_private(), params);
 
-    println!("----------------- Private Key -----------------");
-    println!("{}", base64::encode(private_key));
+        // Never copy the private key into a longer string, nor let other
+        // users read it
+        timing::measure("write", || {
+            if force {
+                remove_if_exists(public_key)?;
+                remove_if_exists(private_key)?;
+            }
+            let mut public_file = create_output(public_key, |path| {
+                OpenOptions::new().write(true).create_new(true).open(path)
+            })?;
+            writeln!(public_file, "{}", public)?;
+            public_file.sync_all()?;
+            let mut private_file = create_output(private_key, create_private)?;
+            writeln!(private_file, "{}", *private)?;
+            private_file.sync_all()
+        })
+        .expect("failed to write into file");
+
+        // A timeout now only leaves complete key pairs
+        complete_output(public_key);
+        complete_output(private_key);
+    }
+
+    if logger::quiet() {
+        return;
+    }
+
+    println!(
+        "generated key pairs {:0width$} to {:0width$} into {}",
+        1,
+        count,
+        out_dir.display(),
+        width = width
+    );
 }
 
-/// Encrypt a plaintext file
-fn encrypt(file: PathBuf, public_key: PathBuf) {
-    let public_key = read_public_key(public_key);
+/// Panic if a plaintext of `len` bytes is too short or too long to be encrypted
+fn check_plaintext_len(len: u64, params: &EncParams) {
+    let min = params::min_plaintext_len(params);
+    if len < min {
+        panic!(
+            "plaintext too small: {} bytes, {} needs at least {}",
+            len,
+            params::name(params),
+            min
+        );
+    }
+
+    let max = params.max_msg_len() as u64;
+    if len > max {
+        panic!(
+            "plaintext too large: {} bytes, {} can encrypt at most {} (see \
+             `ntru params` or split it with --chunk-size)",
+            len,
+            params::name(params),
+            max
+        );
+    }
+}
 
+/// Encrypt a plaintext read from `input` into `output`
+fn encrypt(
+    input: &mut dyn Read,
+    output: &mut dyn Write,
+    public_key: &PublicKey,
+    params: &EncParams,
+    chunk_size: Option<usize>,
+    deterministic: bool,
+) -> io::Result<()> {
     // Read plaintext
-    let plaintext = std::fs::read(&file).expect("can't read file");
+    let plaintext =
+        timing::measure("read", || limits::read_to_end(input, "plaintext"))?;
+
+    let max = params.max_msg_len() as usize;
+    let chunk_size = match chunk_size {
+        None => {
+            check_plaintext_len(plaintext.len() as u64, params);
+            max
+        },
+        Some(size) if size == 0 || size > max => panic!(
+            "--chunk-size must be between 1 and {} bytes for {}",
+            max,
+            params::name(params)
+        ),
+        Some(size) => size,
+    };
+
+    // An empty plaintext still gets a record
+    let chunks = match plaintext.is_empty() {
+        true => vec![&[][..]],
+        false => plaintext.chunks(chunk_size).collect(),
+    };
+
+    info!(
+        "encrypting {} bytes into {} record(s) with {} for public key {}",
+        plaintext.len(),
+        chunks.len(),
+        params::profile(params),
+        fingerprint(public_key, params)
+    );
+
+    // Encrypt: plaintext -> ciphertext, chunk by chunk
+    let rng = match deterministic {
+        true => entropy::convergent(&public_key.export(params), &plaintext)
+            .expect("failed to seed rng"),
+        false => get_rng(),
+    };
+    let ciphertext = timing::measure("crypto", || {
+        chunks
+            .iter()
+            .map(|chunk| ntru::encrypt(chunk, public_key, params, &rng))
+            .collect::<Result<Vec<_>, _>>()
+    })
+    .expect("failed to encrypt")
+    .concat();
+
+    // `decrypt` splits its input into records of exactly this length
+    debug_assert_eq!(
+        ciphertext.len(),
+        chunks.len() * params.enc_len() as usize
+    );
+
+    timing::measure("write", || output.write_all(&ciphertext))
+}
+
+/// Decrypt a ciphertext read from `input` into `output`
+fn decrypt(
+    input: &mut dyn Read,
+    output: &mut dyn Write,
+    key_pair: &KeyPair,
+    params: &EncParams,
+) -> io::Result<()> {
+    // Read ciphertext
+    let ciphertext =
+        timing::measure("read", || limits::read_to_end(input, "ciphertext"))?;
+
+    // libntru reads exactly `enc_len()` bytes whatever the actual length is,
+    // longer files are several ciphertexts concatenated by other tools
+    let expected = params.enc_len() as usize;
+    if ciphertext.is_empty() || ciphertext.len() % expected != 0 {
+        // Tell if it rather looks like ciphertexts of another parameter set
+        let others = params::from_ciphertext_len(ciphertext.len())
+            .into_iter()
+            .map(params::name)
+            .collect::<Vec<_>>();
+        let hint = match &others[..] {
+            [] => String::new(),
+            others => format!(
+                ", which matches ciphertexts of {} (wrong key?)",
+                others.join(", ")
+            ),
+        };
+
+        panic!(
+            "invalid ciphertext length: expected a multiple of {} bytes for \
+             {}, got {}{}",
+            expected,
+            params::name(params),
+            ciphertext.len(),
+            hint
+        );
+    }
+
+    info!(
+        "decrypting {} record(s) with {} for public key {}",
+        ciphertext.len() / expected,
+        params::profile(params),
+        fingerprint(key_pair.get_public(), params)
+    );
 
-    // Encrypt: plaintext -> ciphertext
-    let ciphertext = ntru::encrypt(
-        &plaintext,
+    // Decrypt: ciphertext -> plaintext, record by record
+    let plaintext = timing::measure("crypto", || {
+        ciphertext
+            .chunks(expected)
+            .map(|record| ntru::decrypt(record, key_pair, params))
+            .collect::<Result<Vec<_>, _>>()
+    })
+    .expect("failed to decrypt")
+    .concat();
+
+    timing::measure("write", || output.write_all(&plaintext))
+}
+
+/// Tell if a file argument is `-`, which stands for stdin or stdout
+fn is_stdio(file: &Path) -> bool { file == Path::new("-") }
+
+/// Read a whole input file, or stdin if it is `-`
+fn read_input(file: &Path, what: &str) -> Vec<u8> {
+    if !is_stdio(file) {
+        return timing::measure("read", || {
+            limits::read_to_end(&mut open_input(file, "can't read file"), what)
+        })
+        .expect("can't read file");
+    }
+
+    // Without a pipe, the user may not know that input is expected
+    let stdin = io::stdin();
+    if stdin.is_terminal() && !logger::quiet() {
+        eprintln!("reading {} from terminal, press Ctrl-D when done", what);
+    }
+
+    timing::measure("read", || limits::read_to_end(&mut stdin.lock(), what))
+        .expect("can't read stdin")
+}
+
+/// Write a whole output file atomically, or to stdout if it is `-`, with the
+/// permissions of the `input` file it comes from if it is a new file
+fn write_output(output: &Path, content: &[u8], input: &Path) {
+    timing::measure("write", || match (is_stdio(output), fd_of(output)) {
+        (true, _) => {
+            let stdout = io::stdout().lock();
+            let mut stdout =
+                BufWriter::with_capacity(limits::io_buffer(), stdout);
+            stdout.write_all(content).and_then(|_| stdout.flush())
+        },
+        (_, Some(fd)) => write_fd(fd, content),
+        _ => write_atomically(output, content, input),
+    })
+    .expect("failed to write output");
+}
+
+/// Get the path standing for an inherited file descriptor, as `--output-fd`
+fn fd_path(fd: i32) -> PathBuf { PathBuf::from(format!("/dev/fd/{}", fd)) }
+
+/// Get the file descriptor of a `/dev/fd/<fd>` path
+fn fd_of(path: &Path) -> Option<i32> {
+    path.strip_prefix("/dev/fd").ok()?.to_str()?.parse().ok()
+}
+
+/// Write to an inherited file descriptor, leaving it open for its owner
+#[cfg(unix)]
+fn write_fd(fd: i32, content: &[u8]) -> io::Result<()> {
+    use std::os::fd::BorrowedFd;
+
+    // SAFETY: `fcntl` only reads the flags of the descriptor, failing with
+    // `EBADF` if it isn't open
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    if flags & libc::O_ACCMODE == libc::O_RDONLY {
+        return Err(io::Error::other(format!("fd {} isn't writable", fd)));
+    }
+
+    // SAFETY: `fcntl` just checked that the descriptor is open, and nothing
+    // closes it while it's borrowed to be duplicated, the duplicate being
+    // closed instead of the inherited descriptor
+    let borrowed = unsafe { BorrowedFd::borrow_raw(fd) };
+    let mut file = File::from(borrowed.try_clone_to_owned()?);
+
+    // Only regular files can be truncated back on timeout, not pipes, and
+    // never below their current length, e.g. if opened to append
+    let path = fd_path(fd);
+    let metadata = file.metadata()?;
+    if metadata.is_file() {
+        let len = file.stream_position()?.max(metadata.len());
+        let file = file.try_clone()?;
+        let path = path.clone();
+        partial_outputs().push(Partial::Truncate { path, file, len });
+    }
+
+    // Like other output files, regular files are synced before succeeding
+    let mut output = BufWriter::with_capacity(limits::io_buffer(), file);
+    let result = output.write_all(content).and_then(|_| output.flush());
+    let result = match metadata.is_file() {
+        true => result.and_then(|_| output.get_ref().sync_all()),
+        false => result,
+    };
+    complete_output(&path);
+    result
+}
+
+/// Writing to a file descriptor is only supported on Unix
+#[cfg(not(unix))]
+fn write_fd(_fd: i32, _content: &[u8]) -> io::Result<()> {
+    Err(io::Error::other("--output-fd is only supported on Unix"))
+}
+
+/// Get the file written by `enc` & `dec`: `file` itself if `in_place` or if
+/// it is `-`, otherwise `sibling` which mustn't exist unless `force` is set
+fn output_path(
+    file: &Path,
+    in_place: bool,
+    force: bool,
+    sibling: PathBuf,
+) -> PathBuf {
+    if remote::is_url(file) {
+        panic!(
+            "can't write into or next to {}, which is an URL, see --output-fd",
+            file.display()
+        );
+    }
+
+    if in_place || is_stdio(file) {
+        return file.to_owned();
+    }
+
+    if !force && sibling.exists() {
+        panic!(
+            "{} already exists, use --force to overwrite it",
+            sibling.display()
+        );
+    }
+
+    sibling
+}
+
+/// Append an extension to a path, keeping any existing one
+fn with_added_extension(file: &Path, extension: &str) -> PathBuf {
+    let mut path = file.as_os_str().to_owned();
+    path.push(".");
+    path.push(extension);
+    PathBuf::from(path)
+}
+
+/// Refuse to read or write data through a key file, e.g. `ntru enc key.pub
+/// key.pub` which would overwrite the public key with its own ciphertext
+fn check_not_keys(data: &[&Path], keys: &[&Path]) {
+    let canonical = |path: &Path| match is_stdio(path) || remote::is_url(path) {
+        true => None,
+        false => path.canonicalize().ok(),
+    };
+
+    for key in keys.iter().filter_map(|key| canonical(key)) {
+        if data
+            .iter()
+            .any(|file| canonical(file).as_ref() == Some(&key))
+        {
+            panic!(
+                "{} is a key file, refusing to use it as input or output",
+                key.display()
+            );
+        }
+    }
+}
+
+/// Get the path of the file encrypted from `file`
+fn encrypted_path(file: &Path) -> PathBuf { with_added_extension(file, "enc") }
+
+/// Get the path of the file decrypted from `file`
+fn decrypted_path(file: &Path) -> PathBuf {
+    match file.extension() {
+        Some(extension) if extension == "enc" => file.with_extension(""),
+        _ => with_added_extension(file, "dec"),
+    }
+}
+
+/// Encrypt a plaintext file into `output`, which may be the same file
+fn encrypt_file(
+    file: &Path,
+    output: &Path,
+    public_key: &mut dyn Read,
+    params: &EncParams,
+    armor: bool,
+    chunk_size: Option<usize>,
+    deterministic: bool,
+) {
+    // Fail before reading anything if the file can't be encrypted anyway
+    if !is_stdio(file) && !remote::is_url(file) && chunk_size.is_none() {
+        let metadata = std::fs::metadata(file).expect("can't read file");
+        check_plaintext_len(metadata.len(), params);
+    }
+
+    let public_key = read_public_key(public_key, params);
+
+    let plaintext = read_input(file, "plaintext");
+    let mut ciphertext = Vec::new();
+    encrypt(
+        &mut &plaintext[..],
+        &mut ciphertext,
         &public_key,
-        &DEFAULT_PARAMS_256_BITS,
-        &get_rng(),
+        params,
+        chunk_size,
+        deterministic,
     )
-    .expect("failed to encrypt");
+    .expect("can't read file");
+    if armor {
+        ciphertext = encoding::armor(&ciphertext).into_bytes();
+    }
 
-    // Replace plaintext file's content with ciphertext
-    std::fs::write(file, ciphertext).expect("failed to write into file");
+    write_output(output, &ciphertext, file);
 }
 
-/// Decrypt a ciphertext file
-fn decrypt(file: PathBuf, private_key: PathBuf, public_key: PathBuf) {
-    let private_key = read_private_key(private_key);
-    let public_key = read_public_key(public_key);
+/// Decrypt a ciphertext file into `output`, which may be the same file
+fn decrypt_file(
+    file: &Path,
+    output: &Path,
+    private_key: &Path,
+    public_key: &Path,
+    params: &EncParams,
+    armor: bool,
+) {
+    let private_key = read_private_key(
+        &mut open_input(private_key, "can't read private key file"),
+        params,
+    );
+    let public_key = read_public_key(
+        &mut open_input(public_key, "can't read public key file"),
+        params,
+    );
 
     let key_pair = KeyPair::new(private_key, public_key);
 
-    // Read ciphertext
-    let ciphertext = std::fs::read(&file).expect("can't read file");
+    let mut ciphertext = read_input(file, "ciphertext");
+    if armor {
+        ciphertext =
+            encoding::dearmor(&ciphertext).expect("invalid armored ciphertext");
+    }
+    let mut plaintext = Vec::new();
+    decrypt(&mut &ciphertext[..], &mut plaintext, &key_pair, params)
+        .expect("can't read file");
 
-    // Decrypt: ciphertext -> plaintext
-    let plaintext =
-        ntru::decrypt(&ciphertext, &key_pair, &DEFAULT_PARAMS_256_BITS)
-            .expect("failed to decrypt");
+    write_output(output, &plaintext, file);
+}
+
+/// Decrypt a ciphertext file with the old keys & encrypt the plaintext with the
+/// new public key into `output`, which may be the same file
+fn migrate_file(
+    file: &Path,
+    output: &Path,
+    (old_private_key, old_public_key, old_params): (&Path, &Path, &EncParams),
+    (new_public_key, new_params): (&Path, &EncParams),
+) {
+    let private_key = read_private_key(
+        &mut open_input(old_private_key, "can't read private key file"),
+        old_params,
+    );
+    let public_key = read_public_key(
+        &mut open_input(old_public_key, "can't read public key file"),
+        old_params,
+    );
+    let new_public_key = read_public_key(
+        &mut open_input(new_public_key, "can't read public key file"),
+        new_params,
+    );
+
+    let key_pair = KeyPair::new(private_key, public_key);
 
-    // Replace ciphertext file's content with plaintext
-    std::fs::write(file, plaintext).expect("failed to write into file");
+    let ciphertext = read_input(file, "ciphertext");
+    let mut plaintext = Vec::new();
+    decrypt(&mut &ciphertext[..], &mut plaintext, &key_pair, old_params)
+        .expect("can't read file");
+
+    // The plaintext may be several records long, or too long for a single
+    // record of the new parameter set
+    let mut ciphertext = Vec::new();
+    encrypt(
+        &mut &plaintext[..],
+        &mut ciphertext,
+        &new_public_key,
+        new_params,
+        Some(new_params.max_msg_len() as usize),
+        false,
+    )
+    .expect("can't read file");
+
+    write_output(output, &ciphertext, file);
 }
 
-/// Print general information
-fn print_general_information() {
-    let x = DEFAULT_PARAMS_256_BITS;
-    let backend = "libntru (https://github.com/tbuktu/libntru)";
+/// Overwrite a file with random data then zeros, sync it and delete it
+fn wipe_file(file: PathBuf) {
+    // Never follow a symbolic link, its target might not be what the user
+    // expects, and check what has actually been opened as the file could have
+    // been replaced since any earlier check. Never wait for a FIFO either.
+    let mut options = OpenOptions::new();
+    options.write(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::custom_flags(
+        &mut options,
+        libc::O_NOFOLLOW | libc::O_NONBLOCK,
+    );
+    #[cfg(not(unix))]
+    if std::fs::symlink_metadata(&file).is_ok_and(|m| m.is_symlink()) {
+        panic!("can only wipe regular files");
+    }
+
+    let mut output = match options.open(&file) {
+        #[cfg(unix)]
+        Err(e) if e.raw_os_error() == Some(libc::ELOOP) => {
+            panic!("can only wipe regular files")
+        },
+        result => result.expect("can't open file"),
+    };
+    let metadata = output.metadata().expect("can't read file metadata");
+    if !metadata.is_file() {
+        panic!("can only wipe regular files");
+    }
+    let len = metadata.len();
+
+    // A timeout removes the file without finishing overwriting it
+    partial_outputs().push(Partial::Remove(file.clone()));
+    let rng = get_rng();
+
+    // First pass: random data
+    let mut remaining = len;
+    while remaining > 0 {
+        let chunk = remaining.min(u16::MAX as u64) as u16;
+        let random =
+            ntru::rand::generate(chunk, &rng).expect("failed to generate data");
+        output
+            .write_all(&random)
+            .expect("failed to write into file");
+        remaining -= chunk as u64;
+    }
+    output.sync_all().expect("failed to sync file");
 
-    // Should always be 3
+    // Second pass: zeros
+    output
+        .seek(SeekFrom::Start(0))
+        .expect("failed to rewind file");
+    io::copy(&mut io::repeat(0).take(len), &mut output)
+        .expect("failed to write into file");
+    output.sync_all().expect("failed to sync file");
+
+    drop(output);
+    std::fs::remove_file(&file).expect("failed to remove file");
+    complete_output(&file);
+}
+
+/// Print general information
+fn print_general_information(x: &EncParams, stable: bool) {
+    // libntru hardcodes the small modulus, its parameters have no field for it
     let p = 3;
 
-    println!("     parameter set name :: {}", x.get_name());
-    println!("    ntruencrypt backend :: {}", backend);
+    if stable {
+        println!("name={}", params::name(x));
+        println!("backend={}", BACKEND);
+        println!("public_key_len={}", x.public_len());
+        println!("private_key_len={}", x.private_len());
+        println!("ciphertext_len={}", x.enc_len());
+        println!("max_plaintext_len={}", x.max_msg_len());
+        println!("db={}", x.get_db());
+        println!("n={}", x.get_n());
+        println!("p={}", p);
+        println!("q={}", x.get_q());
+        println!("oid={}", dump::oid(x));
+        return;
+    }
+
+    println!("     parameter set name :: {}", params::name(x));
+    println!("    ntruencrypt backend :: {}", BACKEND);
     println!("      public key length :: {}", x.public_len());
     println!("     private key length :: {}", x.private_len());
     println!("      ciphertext length :: {}", x.enc_len());
     println!("   max plaintext length :: {}", x.max_msg_len());
     println!("random left bit padding :: {}", x.get_db());
-    println!("polynomial coefficients :: {} = N", x.get_n());
-    println!("        smaller modulus :: {}    = p", p);
-    println!("         larger modulus :: {} = q", x.get_q());
+    println!("polynomial coefficients :: {:<4} = N", x.get_n());
+    println!("        smaller modulus :: {:<4} = p", p);
+    println!("         larger modulus :: {:<4} = q", x.get_q());
+    println!("      EESS #1 object id :: {}", dump::oid(x));
+}
+
+/// Print every parameter set with its security level & lengths in bytes
+fn print_parameter_sets(json: bool, by_security: bool) {
+    let ids = match by_security {
+        true => ParamSetId::by_security(),
+        false => ParamSetId::ALL.to_vec(),
+    };
+
+    if json {
+        let sets = ids
+            .iter()
+            .map(|&id| {
+                let params = id.params();
+                serde_json::json!({
+                    "name": id.name(),
+                    "security_bits": id.security_bits(),
+                    "public_key_len": params.public_len(),
+                    "private_key_len": params.private_len(),
+                    "ciphertext_len": params.enc_len(),
+                    "max_plaintext_len": params.max_msg_len(),
+                    "oid": dump::oid(params),
+                    "default": id == ParamSetId::DEFAULT,
+                })
+            })
+            .collect::<Vec<_>>();
+        let sets = serde_json::to_string_pretty(&sets)
+            .expect("failed to serialize parameter sets");
+        return println!("{}", sets);
+    }
+
+    println!(
+        "{:<10} {:>8} {:>10} {:>11} {:>10} {:>13}",
+        "name",
+        "security",
+        "public key",
+        "private key",
+        "ciphertext",
+        "max plaintext"
+    );
+    for id in ids {
+        let params = id.params();
+        println!(
+            "{:<10} {:>3} bits {:>10} {:>11} {:>10} {:>13}{}",
+            id.name(),
+            id.security_bits(),
+            params.public_len(),
+            params.private_len(),
+            params.enc_len(),
+            params.max_msg_len(),
+            if id == ParamSetId::DEFAULT {
+                " (default)"
+            } else {
+                ""
+            }
+        );
+    }
+}
+
+/// Encrypt & decrypt `count` random messages with a fresh key pair, each from
+/// a deterministic RNG whose seed is reported if it fails, panicking if any did
+///
+/// Given the seeds of a failure, replay it instead.
+fn run_selftest(
+    count: u32,
+    key_seed: Option<entropy::Seed>,
+    message_seed: Option<entropy::Seed>,
+    params: &EncParams,
+) {
+    let rng = get_rng();
+    let new_seed =
+        || entropy::Seed::generate(&rng).expect("failed to generate seed");
+
+    let key_seed = key_seed.unwrap_or_else(new_seed);
+    let key_pair = selftest_key_pair(&key_seed, params);
+
+    let count = match message_seed {
+        Some(_) => 1,
+        None => count,
+    };
+    let mut failures = 0;
+    for i in 1..=count {
+        let seed = message_seed.unwrap_or_else(new_seed);
+        if let Err(e) = selftest_round_trip(&seed, &key_pair, params) {
+            failures += 1;
+            warn!(
+                "round-trip {} failed, {}, replay it with: --key-seed {} \
+                 --message-seed {}",
+                i, e, key_seed, seed
+            );
+        }
+    }
+
+    println!(
+        "{} round-trip(s) with {}, {} failure(s)",
+        count,
+        params::profile(params),
+        failures
+    );
+    if failures > 0 {
+        panic!("{} round-trip(s) failed", failures);
+    }
+}
+
+/// Generate the key pair of `selftest` from its seed
+fn selftest_key_pair(seed: &entropy::Seed, params: &EncParams) -> KeyPair {
+    let rng = entropy::seeded(&seed.0).expect("failed to seed rng");
+    ntru::generate_key_pair(params, &rng).expect("failed to generate key pair")
+}
+
+/// Generate a random message of `selftest` from its seed, encrypt it with the
+/// same RNG & decrypt it, returning its ciphertext if it round-trips
+fn selftest_round_trip(
+    seed: &entropy::Seed,
+    key_pair: &KeyPair,
+    params: &EncParams,
+) -> Result<Box<[u8]>, String> {
+    let rng = entropy::seeded(&seed.0).expect("failed to seed rng");
+    let message = ntru::rand::generate(params.max_msg_len() as u16, &rng)
+        .expect("failed to generate message");
+
+    let ciphertext =
+        ntru::encrypt(&message, key_pair.get_public(), params, &rng)
+            .map_err(|e| e.to_string())?;
+    match ntru::decrypt(&ciphertext, key_pair, params) {
+        Ok(plaintext) if plaintext == message => Ok(ciphertext),
+        Ok(_) => Err("the decrypted message differs".to_owned()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Encrypt & decrypt `trials` random messages with a fresh key pair, then
+/// print how many didn't round-trip with a 95% confidence interval
+fn measure_failure_rate(trials: u32, params: &EncParams) {
+    let failures = count_failures(trials, params);
+    let rate = failures as f64 / trials as f64;
+    let (low, high) = confidence_interval(failures, trials);
+
+    println!("     parameter set name :: {}", params::name(params));
+    println!("                 trials :: {}", trials);
+    println!("               failures :: {}", failures);
+    println!("           failure rate :: {:.2e}", rate);
+    println!("95% confidence interval :: [{:.2e}, {:.2e}]", low, high);
+}
+
+/// Encrypt & decrypt `trials` random messages with a fresh key pair, returning
+/// how many didn't round-trip
+fn count_failures(trials: u32, params: &EncParams) -> u32 {
+    if trials == 0 {
+        panic!("--trials must be at least 1");
+    }
+
+    let rng = get_rng();
+    let key_pair =
+        generate("key pair", |rng| ntru::generate_key_pair(params, rng));
+
+    let mut failures = 0;
+    for _ in 0..trials {
+        let message = ntru::rand::generate(params.max_msg_len() as u16, &rng)
+            .expect("failed to generate message");
+        let ciphertext =
+            ntru::encrypt(&message, key_pair.get_public(), params, &rng)
+                .expect("failed to encrypt");
+        match ntru::decrypt(&ciphertext, &key_pair, params) {
+            Ok(plaintext) if plaintext == message => (),
+            _ => failures += 1,
+        }
+    }
+
+    failures
+}
+
+/// Get the 95% confidence interval of a failure rate, a Wilson score interval
+/// still meaningful when no failure is observed
+fn confidence_interval(failures: u32, trials: u32) -> (f64, f64) {
+    let (n, z) = (trials as f64, 1.96);
+    let rate = failures as f64 / n;
+    let center = (rate + z * z / (2.0 * n)) / (1.0 + z * z / n);
+    let margin = z * (rate * (1.0 - rate) / n + z * z / (4.0 * n * n)).sqrt()
+        / (1.0 + z * z / n);
+
+    ((center - margin).max(0.0), center + margin)
+}
+
+/// Print the version of this build, then run a pass/fail check of the
+/// parameter sets, of the RNG & of a round-trip with `params`, panicking if any
+/// failed
+fn run_doctor(params: &EncParams) {
+    print_version_information(false);
+
+    let rng =
+        entropy::init().map_err(|e| format!("can't initialize it: {}", e));
+
+    // Two draws of a working RNG never match
+    let rng_check = rng.as_ref().map_err(Clone::clone).and_then(|rng| {
+        let draw = || ntru::rand::generate(32, rng).map_err(|e| e.to_string());
+        match draw()? == draw()? {
+            true => Err("it returned the same bytes twice".to_owned()),
+            false => Ok(()),
+        }
+    });
+
+    let round_trip = rng.and_then(|rng| {
+        let message = b"ntru doctor";
+        let plaintext = ntru::generate_key_pair(params, &rng)
+            .and_then(|key_pair| {
+                let public_key = key_pair.get_public();
+                let ciphertext =
+                    ntru::encrypt(message, public_key, params, &rng)?;
+                ntru::decrypt(&ciphertext, &key_pair, params)
+            })
+            .map_err(|e| e.to_string())?;
+
+        match *plaintext == message[..] {
+            true => Ok(()),
+            false => Err("the decrypted message differs".to_owned()),
+        }
+    });
+
+    let checks = [
+        ("parameter sets".to_owned(), params::self_check()),
+        ("RNG".to_owned(), rng_check),
+        (format!("{} round-trip", params::name(params)), round_trip),
+    ];
+    let mut failures = 0;
+    for (check, result) in &checks {
+        match result {
+            Ok(()) => println!("{:>23} :: pass", check),
+            Err(e) => {
+                failures += 1;
+                println!("{:>23} :: FAIL, {}", check, e);
+            },
+        }
+    }
+
+    if failures > 0 {
+        panic!("{} check(s) failed", failures);
+    }
+}
+
+/// Versions & features of this build
+#[derive(Serialize)]
+struct VersionInfo {
+    crate_version: &'static str,
+    backend: &'static str,
+    features: &'static [&'static str],
+}
+
+/// Get the versions & features of this build
+fn version_info() -> VersionInfo {
+    VersionInfo {
+        crate_version: env!("CARGO_PKG_VERSION"),
+        backend: BACKEND,
+        features: FEATURES,
+    }
+}
+
+/// Print the versions & features of this build
+fn print_version_information(json: bool) {
+    let info = version_info();
+    if json {
+        let info = serde_json::to_string_pretty(&info)
+            .expect("failed to serialize version information");
+        return println!("{}", info);
+    }
+
+    let features = match info.features {
+        [] => "none".to_owned(),
+        features => features.join(", "),
+    };
+
+    println!("           ntru version :: {}", info.crate_version);
+    println!("    ntruencrypt backend :: {}", info.backend);
+    println!("       enabled features :: {}", features);
+}
+
+/// Fill in the options missing from the command line with the values of the
+/// config file, so that explicit arguments & `NTRU_PARAMS` always win
+///
+/// Flags such as `--armor` can only be turned on, not off, by the command line.
+fn apply_config(opt: &mut Opt, config: &Config) {
+    opt.params = opt.params.or(config.params);
+    opt.encoding = opt.encoding.or(config.encoding);
+
+    match &mut opt.cmd {
+        Command::Enc { armor, .. } | Command::Dec { armor, .. } => {
+            *armor |= config.armor == Some(true)
+        },
+        _ => (),
+    }
 }
 
 fn main() {
-    let opt = Opt::from_args();
+    // Report invalid command lines like any other usage error, even though
+    // `--json-errors` itself can't be parsed then
+    let mut opt =
+        Opt::from_iter_safe(std::env::args_os()).unwrap_or_else(|e| {
+            let json = std::env::args_os().any(|arg| arg == "--json-errors");
+            logger::usage_error(e, json)
+        });
+    logger::report_errors(opt.json_errors);
+
+    if let Some(timeout) = opt.timeout {
+        start_watchdog(Duration::from_secs(timeout));
+    }
+
+    let config = opt.config.take().map(config::load).unwrap_or_default();
+    apply_config(&mut opt, &config);
+
+    logger::init(match (opt.quiet, opt.verbose) {
+        (true, _) => LevelFilter::Error,
+        (_, true) => LevelFilter::Info,
+        _ => LevelFilter::Warn,
+    });
+
+    if let Some(encoding) = opt.encoding {
+        encoding::select(encoding);
+    }
+
+    if let Some(algorithm) = opt.fingerprint {
+        fingerprint::select(algorithm);
+    }
+
+    if let Some(max_input) = opt.max_input {
+        limits::set_max_input(max_input);
+    }
+
+    if let Some(extra_entropy) = opt.extra_entropy {
+        let mut file = open(&extra_entropy, "can't read extra entropy file");
+        let extra = limits::read_secret(&mut file, "extra entropy")
+            .expect("can't read extra entropy file");
+        entropy::add(extra);
+    }
+
+    if let Some(io_buffer) = opt.io_buffer {
+        limits::set_io_buffer(io_buffer);
+    }
+
+    if opt.time || opt.verbose {
+        timing::enable();
+    }
+
+    let id = opt.params;
+    let params = id.unwrap_or(ParamSetId::DEFAULT).params();
+
+    // Without an explicit parameter set, infer it from the public key
+    let params_for = |public_key: &Path| match id {
+        None => infer_params(&mut open_input(
+            public_key,
+            "can't read public key file",
+        )),
+        Some(_) => params,
+    };
+    let public_key = |maybe_key: Option<PathBuf>| {
+        maybe_key
+            .or(config.public_key.clone())
+            .expect("no public key file given")
+    };
+    let private_key = |maybe_key: Option<PathBuf>| {
+        maybe_key
+            .or(config.private_key.clone())
+            .expect("no private key file given")
+    };
 
     // Execute the correct function depending on the arguments
-    match opt {
-        Opt::Gen { private_key } => match private_key {
-            None => generate_key_pair(),
-            Some(private_key) => {
-                generate_key_pair_from_private_key(private_key)
-            },
+    match opt.cmd {
+        Command::Gen {
+            count: Some(count),
+            out_dir: Some(out_dir),
+            force,
+            ..
+        } => generate_key_pairs(count, &out_dir, force, params),
+        Command::Gen { private_key, .. } => {
+            let mut output = io::stdout().lock();
+            match private_key {
+                None => generate_key_pair(&mut output, params),
+                Some(private_key) => generate_key_pair_from_private_key(
+                    &mut open_input(
+                        &private_key,
+                        "can't read private key file",
+                    ),
+                    &mut output,
+                    params,
+                ),
+            }
+            .expect("failed to write output")
+        },
+        Command::Enc {
+            file,
+            in_place,
+            force,
+            armor,
+            output_fd,
+            chunk_size,
+            deterministic,
+            public_key: maybe_key,
+            recipient_key,
+            recipient_fp,
+            keyring,
+        } => {
+            let output = match output_fd {
+                Some(fd) => fd_path(fd),
+                None => {
+                    output_path(&file, in_place, force, encrypted_path(&file))
+                },
+            };
+
+            let (mut public_key, params): (Box<dyn Read>, _) =
+                match (recipient_key, recipient_fp, keyring) {
+                    (Some(key), ..) => {
+                        warn!("--recipient-key is visible to other users");
+                        let params = match id {
+                            None => infer_params(&mut key.as_bytes()),
+                            Some(_) => params,
+                        };
+                        (Box::new(io::Cursor::new(key)), params)
+                    },
+                    (_, Some(fingerprint), Some(keyring)) => {
+                        let keyring = Keyring::load(&keyring);
+                        let path = keyring.find(&fingerprint);
+                        check_not_keys(&[&file, &output], &[path]);
+                        let key =
+                            open_input(path, "can't read public key file");
+                        (key, params_for(path))
+                    },
+                    _ => {
+                        let path = public_key(maybe_key);
+                        check_not_keys(&[&file, &output], &[&path]);
+                        let key =
+                            open_input(&path, "can't read public key file");
+                        (key, params_for(&path))
+                    },
+                };
+            encrypt_file(
+                &file,
+                &output,
+                &mut public_key,
+                params,
+                armor,
+                chunk_size,
+                deterministic,
+            )
         },
-        Opt::Enc { file, public_key } => encrypt(file, public_key),
-        Opt::Dec {
+        Command::Dec {
             file,
-            private_key,
+            in_place,
+            force,
+            print,
+            armor,
+            output_fd,
+            private_key: maybe_private_key,
+            public_key: maybe_public_key,
+        } => {
+            let private_key = private_key(maybe_private_key);
+            let public_key = public_key(maybe_public_key);
+            let params = params_for(&public_key);
+            let output = match (print, output_fd) {
+                (true, _) => PathBuf::from("-"),
+                (_, Some(fd)) => fd_path(fd),
+                _ => output_path(&file, in_place, force, decrypted_path(&file)),
+            };
+            check_not_keys(&[&file, &output], &[&private_key, &public_key]);
+            decrypt_file(
+                &file,
+                &output,
+                &private_key,
+                &public_key,
+                params,
+                armor,
+            )
+        },
+        Command::Migrate {
+            file,
+            old_private_key,
+            old_public_key,
+            new_public_key,
+            old_params,
+            new_params,
+            output,
+        } => {
+            check_not_keys(
+                &[&file, &output],
+                &[&old_private_key, &old_public_key, &new_public_key],
+            );
+            let old_params = match old_params.or(id) {
+                Some(id) => id.params(),
+                None => infer_params(&mut open_input(
+                    &old_public_key,
+                    "can't read public key file",
+                )),
+            };
+            let new_params = match new_params {
+                Some(id) => id.params(),
+                None => infer_params(&mut open_input(
+                    &new_public_key,
+                    "can't read public key file",
+                )),
+            };
+            migrate_file(
+                &file,
+                &output,
+                (&old_private_key, &old_public_key, old_params),
+                (&new_public_key, new_params),
+            )
+        },
+        Command::Wipe { file } => wipe_file(file),
+        Command::Info { stable } => print_general_information(params, stable),
+        Command::Params { json, by_security } => {
+            print_parameter_sets(json, by_security)
+        },
+        Command::Version { json } => print_version_information(json),
+        Command::Doctor => run_doctor(params),
+        Command::OneLine {
             public_key,
-        } => decrypt(file, private_key, public_key),
-        Opt::Info => print_general_information(),
+            comment,
+        } => {
+            let params = params_for(&public_key);
+            let public_key = read_public_key(
+                &mut open_input(&public_key, "can't read public key file"),
+                params,
+            );
+            println!(
+                "{}",
+                encoding::to_line(
+                    &params::name(params),
+                    &public_key.export(params),
+                    &comment.join(" ")
+                )
+            );
+        },
+        Command::Selftest {
+            count,
+            key_seed,
+            message_seed,
+        } => run_selftest(count, key_seed, message_seed, params),
+        Command::FailureRate { trials } => measure_failure_rate(trials, params),
+        Command::DumpParams { name } => {
+            print!(
+                "{}",
+                dump::debug_dump(name.map_or(params, ParamSetId::params))
+            )
+        },
+    }
+
+    timing::report();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic::AssertUnwindSafe;
+
+    use super::*;
+
+    /// Get an empty directory for the files of the test `name`
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "ntru-test-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Generate a key pair of `params` through `generate_key_pair`, entirely in
+    /// memory, returning the keys read back & the written text
+    fn generate_in_memory(params: &EncParams) -> (KeyPair, String) {
+        let mut output = Vec::new();
+        generate_key_pair(&mut output, params).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        // Skip the decoration written unless quiet
+        let keys = output
+            .lines()
+            .filter(|line| !line.is_empty() && !line.starts_with('-'))
+            .collect::<Vec<_>>();
+        let [public_key, private_key] = keys[..] else {
+            panic!("expected 2 keys, got: {}", output);
+        };
+
+        let key_pair = KeyPair::new(
+            read_private_key(&mut private_key.as_bytes(), params),
+            read_public_key(&mut public_key.as_bytes(), params),
+        );
+        (key_pair, output)
+    }
+
+    /// Get the permission bits of a file
+    #[cfg(unix)]
+    fn mode(path: &Path) -> u32 {
+        use std::os::unix::fs::PermissionsExt;
+
+        std::fs::metadata(path).unwrap().permissions().mode() & 0o777
+    }
+
+    #[test]
+    fn generate_retries_rng_failures() {
+        let attempts = std::cell::Cell::new(0);
+        let key = generate("key", |_| {
+            attempts.set(attempts.get() + 1);
+            match attempts.get() {
+                ..=2 => Err(Error::Prng),
+                attempt => Ok(attempt),
+            }
+        });
+        assert_eq!(key, 3);
+
+        // Other errors are permanent
+        attempts.set(0);
+        let failed = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            generate("key", |_| {
+                attempts.set(attempts.get() + 1);
+                Err::<(), _>(Error::InvalidParam)
+            })
+        }));
+        assert_eq!(
+            *failed.unwrap_err().downcast::<String>().unwrap(),
+            "failed to generate key after 1 attempt(s): InvalidParam"
+        );
+        assert_eq!(attempts.get(), 1);
+
+        attempts.set(0);
+        let failed = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            generate("key", |_| {
+                attempts.set(attempts.get() + 1);
+                Err::<(), _>(Error::Prng)
+            })
+        }));
+        assert!(failed.is_err());
+        assert_eq!(attempts.get(), KEYGEN_ATTEMPTS);
+    }
+
+    #[test]
+    fn init_rng_retries_transient_failures() {
+        let attempts = std::cell::Cell::new(0);
+        let rng = init_rng(|| {
+            attempts.set(attempts.get() + 1);
+            match attempts.get() {
+                ..=2 => Err(Error::Prng),
+                attempt => Ok(attempt),
+            }
+        });
+        assert_eq!(rng, 3);
+    }
+
+    #[test]
+    fn init_rng_gives_up_after_its_attempts() {
+        let attempts = std::cell::Cell::new(0);
+        let failed = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            init_rng(|| {
+                attempts.set(attempts.get() + 1);
+                Err::<(), _>(Error::Prng)
+            })
+        }));
+
+        let error = failed.unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<String>().unwrap(),
+            "failed to initialize rng after 5 attempt(s): Prng"
+        );
+        assert_eq!(attempts.get(), RNG_INIT_ATTEMPTS);
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_in_memory() {
+        let params = ParamSetId::Ees401Ep1.params();
+        let (key_pair, _) = generate_in_memory(params);
+
+        let plaintext = b"never written to disk";
+        let mut ciphertext = Vec::new();
+        encrypt(
+            &mut &plaintext[..],
+            &mut ciphertext,
+            key_pair.get_public(),
+            params,
+            None,
+            false,
+        )
+        .unwrap();
+        assert_eq!(ciphertext.len(), params.enc_len() as usize);
+
+        let mut decrypted = Vec::new();
+        decrypt(&mut &ciphertext[..], &mut decrypted, &key_pair, params)
+            .unwrap();
+        assert_eq!(decrypted, plaintext);
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_chunks_in_memory() {
+        let params = ParamSetId::Ees401Ep1.params();
+        let (key_pair, _) = generate_in_memory(params);
+
+        let plaintext = (0..=255).cycle().take(100).collect::<Vec<u8>>();
+        let mut ciphertext = Vec::new();
+        encrypt(
+            &mut &plaintext[..],
+            &mut ciphertext,
+            key_pair.get_public(),
+            params,
+            Some(30),
+            false,
+        )
+        .unwrap();
+        assert_eq!(ciphertext.len(), 4 * params.enc_len() as usize);
+
+        let mut decrypted = Vec::new();
+        decrypt(&mut &ciphertext[..], &mut decrypted, &key_pair, params)
+            .unwrap();
+        assert_eq!(decrypted, plaintext);
+    }
+
+    #[test]
+    fn convergent_encryption_is_reproducible() {
+        let params = ParamSetId::Ees1171Ep1.params();
+        let public_key = read_public_key(
+            &mut open(&fixture("key/public.txt"), "no public key"),
+            params,
+        );
+        let encrypt_convergent = || {
+            let mut ciphertext = Vec::new();
+            encrypt(
+                &mut &b"same plaintext"[..],
+                &mut ciphertext,
+                &public_key,
+                params,
+                None,
+                true,
+            )
+            .unwrap();
+            ciphertext
+        };
+
+        // Initializing the default RNG in between changes nothing
+        let ciphertext = encrypt_convergent();
+        drop(get_rng());
+        assert_eq!(encrypt_convergent(), ciphertext);
+        assert_eq!(encrypt_convergent(), ciphertext);
+    }
+
+    #[test]
+    fn derive_public_key_in_memory() {
+        let params = ParamSetId::Ees401Ep1.params();
+        let (_, output) = generate_in_memory(params);
+        let private_key = output.lines().rfind(|line| !line.is_empty());
+        let private_key = private_key.unwrap().as_bytes();
+
+        let mut public_key = Vec::new();
+        generate_key_pair_from_private_key(
+            &mut &private_key[..],
+            &mut public_key,
+            params,
+        )
+        .unwrap();
+        let public_key = String::from_utf8(public_key).unwrap();
+        let public_key = public_key.lines().next_back().unwrap();
+
+        // The derived key pairs with the private key it comes from
+        let key_pair = KeyPair::new(
+            read_private_key(&mut &private_key[..], params),
+            read_public_key(&mut public_key.as_bytes(), params),
+        );
+        let mut ciphertext = Vec::new();
+        encrypt(
+            &mut &b"derived"[..],
+            &mut ciphertext,
+            key_pair.get_public(),
+            params,
+            None,
+            false,
+        )
+        .unwrap();
+        let mut decrypted = Vec::new();
+        decrypt(&mut &ciphertext[..], &mut decrypted, &key_pair, params)
+            .unwrap();
+        assert_eq!(decrypted, b"derived");
+    }
+
+    #[test]
+    fn decrypt_failures_are_crypto_errors() {
+        let params = ParamSetId::Ees401Ep1.params();
+        let (key_pair, _) = generate_in_memory(params);
+        let (other_key_pair, _) = generate_in_memory(params);
+
+        let mut ciphertext = Vec::new();
+        encrypt(
+            &mut &b"for someone else"[..],
+            &mut ciphertext,
+            key_pair.get_public(),
+            params,
+            None,
+            false,
+        )
+        .unwrap();
+
+        let failure = std::panic::catch_unwind(|| {
+            let mut plaintext = Vec::new();
+            decrypt(
+                &mut &ciphertext[..],
+                &mut plaintext,
+                &other_key_pair,
+                params,
+            )
+        });
+        let payload = failure.unwrap_err();
+        let message = payload.downcast_ref::<String>().unwrap();
+        assert_eq!(logger::classify(message), ("crypto", 5));
+    }
+
+    #[test]
+    fn decrypt_hints_at_other_parameter_sets() {
+        let params = ParamSetId::Ees1171Ep1.params();
+        let key_pair = KeyPair::new(
+            read_private_key(
+                &mut open(&fixture("key/private.txt"), ""),
+                params,
+            ),
+            read_public_key(&mut open(&fixture("key/public.txt"), ""), params),
+        );
+        let decrypt_len = |len: usize| {
+            std::panic::catch_unwind(|| {
+                decrypt(
+                    &mut &vec![0; len][..],
+                    &mut Vec::new(),
+                    &key_pair,
+                    params,
+                )
+            })
+            .map(|_| ())
+            .map_err(|e| *e.downcast::<String>().unwrap())
+        };
+
+        let other = ParamSetId::Ees439Ep1.params();
+        let len = 2 * other.enc_len() as usize;
+        let error = decrypt_len(len).unwrap_err();
+        assert!(
+            error.starts_with(&format!(
+                "invalid ciphertext length: expected a multiple of {} bytes \
+                 for EES1171EP1, got {}, which matches ciphertexts of",
+                params.enc_len(),
+                len
+            )),
+            "{}",
+            error
+        );
+        assert!(error.contains("EES439EP1") && error.ends_with("(wrong key?)"));
+
+        assert_eq!(
+            decrypt_len(7).unwrap_err(),
+            format!(
+                "invalid ciphertext length: expected a multiple of {} bytes \
+                 for EES1171EP1, got 7",
+                params.enc_len()
+            )
+        );
+    }
+
+    #[test]
+    fn infer_params_from_public_key_lengths() {
+        let infer = |len: u16| {
+            let key = encoding::encode(&vec![0; len as usize]);
+            infer_params(&mut key.as_bytes())
+        };
+
+        // Sets sharing their length, e.g. EES1087EP1 & EES1087EP2, are
+        // ambiguous instead
+        for id in ParamSetId::ALL {
+            let len = id.params().public_len();
+            if params::from_public_key_len(len as usize).len() == 1 {
+                assert_eq!(infer(len), id.params(), "{}", id.name());
+            }
+        }
+
+        // Unknown lengths fall back to the default parameter set
+        assert_eq!(infer(42), ParamSetId::DEFAULT.params());
+    }
+
+    #[test]
+    fn infer_params_refuses_ambiguous_lengths() {
+        let (ep1, ep2) = (ParamSetId::Ees401Ep1, ParamSetId::Ees401Ep2);
+        let len = ep1.params().public_len();
+        assert_eq!(len, ep2.params().public_len());
+
+        let key = encoding::encode(&vec![0; len as usize]);
+        let inferred =
+            std::panic::catch_unwind(|| infer_params(&mut key.as_bytes()));
+        assert_eq!(
+            *inferred.unwrap_err().downcast::<String>().unwrap(),
+            "ambiguous public key size, select one of EES401EP1, EES401EP2 \
+             with --params"
+        );
+
+        // Unless the key names its parameter set
+        let line = encoding::to_line("EES401EP2", &vec![0; len as usize], "");
+        assert_eq!(infer_params(&mut line.as_bytes()), ep2.params());
+    }
+
+    #[test]
+    fn generate_key_pairs_into_directory() {
+        let dir = temp_dir("gen-count");
+        let params = ParamSetId::Ees401Ep1.params();
+        generate_key_pairs(5, &dir, false, params);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 10);
+        assert!(partial_outputs()
+            .iter()
+            .all(|p| !p.path().starts_with(&dir)));
+
+        for i in 1..=5 {
+            let public_key = dir.join(format!("key_{:04}.pub", i));
+            let private_key = dir.join(format!("key_{:04}.priv", i));
+
+            #[cfg(unix)]
+            assert_eq!(mode(&private_key), 0o600);
+
+            let key_pair = KeyPair::new(
+                read_private_key(&mut open(&private_key, "no key"), params),
+                read_public_key(&mut open(&public_key, "no key"), params),
+            );
+            let mut ciphertext = Vec::new();
+            encrypt(
+                &mut &b"provisioned"[..],
+                &mut ciphertext,
+                key_pair.get_public(),
+                params,
+                None,
+                false,
+            )
+            .unwrap();
+            let mut decrypted = Vec::new();
+            decrypt(&mut &ciphertext[..], &mut decrypted, &key_pair, params)
+                .unwrap();
+            assert_eq!(decrypted, b"provisioned");
+        }
+
+        // Existing files are only replaced with --force
+        generate_key_pairs(5, &dir, true, params);
+        let overwrite = std::panic::catch_unwind(|| {
+            generate_key_pairs(5, &dir, false, params)
+        });
+        assert!(overwrite.is_err());
+    }
+
+    /// Path of a file of the `test` directory
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("test")
+            .join(name)
+    }
+
+    #[test]
+    fn public_key_export_is_pinned() {
+        // h = 1 + 2x + 2047x^2 for EES401EP1: N & q as big-endian 16-bit
+        // integers, then the 11-bit coefficients packed from the least
+        // significant bit of each byte
+        let params = ParamSetId::Ees401Ep1.params();
+        let mut export =
+            vec![0x01, 0x91, 0x08, 0x00, 0x01, 0x10, 0xc0, 0xff, 0x01];
+        export.resize(params.public_len() as usize, 0);
+
+        let public_key = PublicKey::import(&export);
+        assert_eq!(public_key.get_q(), 2048);
+        let coeffs = public_key.get_h().get_coeffs();
+        assert_eq!(coeffs.len(), 401);
+        assert_eq!(coeffs[..3], [1, 2, 2047]);
+        assert!(coeffs[3..].iter().all(|&coeff| coeff == 0));
+
+        assert_eq!(*public_key.export(params), export[..]);
+    }
+
+    #[test]
+    fn imported_public_keys_export_unchanged() {
+        // Neither in this process nor anywhere else has this key been paired
+        // with a private key
+        let params = ParamSetId::Ees401Ep1.params();
+        let mut export = vec![0x01, 0x91, 0x08, 0x00];
+        export.extend((0..params.enc_len()).map(|i| (i * 37) as u8));
+
+        // Only 3 bits of the last byte hold coefficients, exports zero the
+        // others
+        *export.last_mut().unwrap() &= 0x07;
+
+        let public_key =
+            read_public_key(&mut encoding::encode(&export).as_bytes(), params);
+        assert_eq!(*public_key.export(params), export[..]);
+
+        let key = std::fs::read_to_string(fixture("key/public.txt")).unwrap();
+        let export = encoding::decode(key.trim()).unwrap();
+        let params = ParamSetId::Ees1171Ep1.params();
+        assert_eq!(*PublicKey::import(&export).export(params), export[..]);
+    }
+
+    #[test]
+    fn keys_followed_by_other_lines_import_unchanged() {
+        let params = ParamSetId::Ees1171Ep1.params();
+        let public =
+            std::fs::read_to_string(fixture("key/public.txt")).unwrap();
+        let private =
+            std::fs::read_to_string(fixture("key/private.txt")).unwrap();
+        let (public, private) = (public.trim(), private.trim());
+
+        for extra in ["\n# comment\nmore lines\n", "\r\n# comment\r\n", "\r\n"]
+        {
+            let text = format!("{}{}", public, extra);
+            let public_key = read_public_key(&mut text.as_bytes(), params);
+            assert_eq!(
+                encoding::encode(&public_key.export(params)),
+                public,
+                "{:?}",
+                extra
+            );
+
+            let text = format!("{}{}", private, extra);
+            let private_key = read_private_key(&mut text.as_bytes(), params);
+            assert_eq!(
+                encoding::encode(&private_key.export(params)),
+                private,
+                "{:?}",
+                extra
+            );
+        }
+    }
+
+    #[test]
+    fn one_line_keys_name_their_parameter_set() {
+        let params = ParamSetId::Ees1171Ep1.params();
+        let key = std::fs::read_to_string(fixture("key/public.txt")).unwrap();
+        let export = encoding::decode(key.trim()).unwrap();
+        let read = |line: String| {
+            std::panic::catch_unwind(|| {
+                read_public_key(&mut line.as_bytes(), params)
+                    .export(params)
+                    .to_vec()
+            })
+            .map_err(|e| *e.downcast::<String>().unwrap())
+        };
+
+        let line = encoding::to_line("EES1171EP1", &export, "comment");
+        assert_eq!(read(line).unwrap(), export);
+        let line = format!("ntru-hps4096821 {}", key.trim());
+        assert_eq!(read(line).unwrap(), export);
+
+        // A name disagreeing with the selected parameter set is refused
+        let line = encoding::to_line("EES401EP1", &export, "");
+        assert_eq!(
+            read(line).unwrap_err(),
+            "public key is for ees401ep1, not EES1171EP1"
+        );
+        let line = format!("ntru-hps2048509 {}", key.trim());
+        assert_eq!(
+            read(line).unwrap_err(),
+            "public key is for hps2048509, not EES1171EP1"
+        );
+    }
+
+    #[test]
+    fn key_files_are_pinned() {
+        let params = ParamSetId::Ees1171Ep1.params();
+        let public_key = read_public_key(
+            &mut open(&fixture("key/public.txt"), "no public key"),
+            params,
+        );
+        let private_key = read_private_key(
+            &mut open(&fixture("key/private.txt"), "no private key"),
+            params,
+        );
+
+        assert_eq!(
+            fingerprint::of_export(&public_key.export(params)),
+            "7bd167ad18c7fc3cccd786126e6466bc1ded0e3a716119336d0a4e5521441c68"
+        );
+        assert_eq!(
+            public_key.get_h().get_coeffs()[..6],
+            [822, 1257, 1772, 389, 263, 1900]
+        );
+
+        let private_poly = private_key.get_t();
+        assert!(!private_poly.is_product());
+        let private_poly = private_poly.get_poly_tern();
+        assert_eq!(private_poly.get_ones()[..4], [39, 97, 581, 1115]);
+        assert_eq!(private_poly.get_neg_ones()[..4], [1066, 99, 281, 485]);
+
+        // A ciphertext written by an earlier version must still decrypt
+        let ciphertext = encoding::dearmor(
+            &std::fs::read(fixture("pinned.enc.txt")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(ciphertext.len(), params.enc_len() as usize);
+        let key_pair = KeyPair::new(private_key, public_key);
+        let plaintext = ntru::decrypt(&ciphertext, &key_pair, params).unwrap();
+        assert_eq!(*plaintext, b"pinned"[..]);
+    }
+
+    #[test]
+    fn migrate_multi_record_files() {
+        let dir = temp_dir("migrate");
+        let old_params = ParamSetId::Ees1171Ep1.params();
+        let new_params = ParamSetId::Ees401Ep1.params();
+        let old_public_key = read_public_key(
+            &mut open(&fixture("key/public.txt"), "no public key"),
+            old_params,
+        );
+
+        // More than a record of either parameter set
+        let plaintext = (0..=255).cycle().take(300).collect::<Vec<u8>>();
+        let mut ciphertext = Vec::new();
+        encrypt(
+            &mut &plaintext[..],
+            &mut ciphertext,
+            &old_public_key,
+            old_params,
+            Some(100),
+            false,
+        )
+        .unwrap();
+        let file = dir.join("file.enc");
+        std::fs::write(&file, &ciphertext).unwrap();
+
+        let (new_key_pair, _) = generate_in_memory(new_params);
+        let new_public_key = dir.join("new_public.txt");
+        let export = new_key_pair.get_public().export(new_params);
+        std::fs::write(&new_public_key, encoding::encode(&export)).unwrap();
+
+        let migrated = dir.join("file.migrated.enc");
+        migrate_file(
+            &file,
+            &migrated,
+            (
+                &fixture("key/private.txt"),
+                &fixture("key/public.txt"),
+                old_params,
+            ),
+            (&new_public_key, new_params),
+        );
+
+        let ciphertext = std::fs::read(&migrated).unwrap();
+        let records =
+            plaintext.len().div_ceil(new_params.max_msg_len() as usize);
+        assert_eq!(ciphertext.len(), records * new_params.enc_len() as usize);
+        let mut decrypted = Vec::new();
+        decrypt(
+            &mut &ciphertext[..],
+            &mut decrypted,
+            &new_key_pair,
+            new_params,
+        )
+        .unwrap();
+        assert_eq!(decrypted, plaintext);
+    }
+
+    #[test]
+    fn selftest_passes() {
+        run_selftest(3, None, None, ParamSetId::DEFAULT.params())
+    }
+
+    #[test]
+    fn selftest_seeds_replay_round_trips() {
+        let params = ParamSetId::Ees401Ep1.params();
+        let key_seed = entropy::Seed::generate(&get_rng()).unwrap();
+        let seed = entropy::Seed::parse(&"2a".repeat(32)).unwrap();
+        let reported = entropy::Seed::parse(&key_seed.to_string()).unwrap();
+        assert_eq!(reported, key_seed);
+        assert!(entropy::Seed::parse("2a").is_err());
+        assert!(entropy::Seed::parse(&"zz".repeat(32)).is_err());
+
+        let key_pair = selftest_key_pair(&key_seed, params);
+        let ciphertext = selftest_round_trip(&seed, &key_pair, params);
+
+        // Whatever ran in between
+        drop(get_rng());
+        let replayed = selftest_key_pair(&reported, params);
+        assert_eq!(
+            replayed.get_public().export(params),
+            key_pair.get_public().export(params)
+        );
+        assert_eq!(
+            *replayed.get_private().export(params),
+            *key_pair.get_private().export(params)
+        );
+        let replay = selftest_round_trip(&seed, &replayed, params);
+        assert_eq!(replay.unwrap(), ciphertext.unwrap());
+
+        run_selftest(100, Some(key_seed), Some(seed), params);
+    }
+
+    #[test]
+    fn wipe_removes_files() {
+        let dir = temp_dir("wipe");
+        let file = dir.join("secret");
+        std::fs::write(&file, vec![0x61; 100_000]).unwrap();
+
+        // A hard link still shows the content after the file is removed
+        let other_link = dir.join("other-link");
+        std::fs::hard_link(&file, &other_link).unwrap();
+        wipe_file(file.clone());
+        assert!(!file.exists());
+        assert_eq!(std::fs::read(&other_link).unwrap(), vec![0; 100_000]);
+
+        // Neither a symbolic link nor its target is wiped
+        #[cfg(unix)]
+        {
+            let target = dir.join("target");
+            std::fs::write(&target, b"kept").unwrap();
+            let link = dir.join("link");
+            std::os::unix::fs::symlink(&target, &link).unwrap();
+
+            let wiped = std::panic::catch_unwind(|| wipe_file(link.clone()));
+            let error = wiped.unwrap_err();
+            assert_eq!(
+                error.downcast_ref::<&str>(),
+                Some(&"can only wipe regular files")
+            );
+            assert!(link.exists());
+            assert_eq!(std::fs::read(&target).unwrap(), b"kept");
+        }
+
+        assert!(std::panic::catch_unwind(|| wipe_file(dir.clone())).is_err());
+    }
+
+    #[test]
+    fn outputs_are_siblings_unless_in_place() {
+        let dir = temp_dir("output-path");
+        let file = dir.join("file.txt");
+        let encrypted = dir.join("file.txt.enc");
+        assert_eq!(encrypted_path(&file), encrypted);
+        assert_eq!(decrypted_path(&encrypted), file);
+        assert_eq!(decrypted_path(&file), dir.join("file.txt.dec"));
+
+        let sibling =
+            || output_path(&file, false, false, encrypted_path(&file));
+        assert_eq!(sibling(), encrypted);
+
+        // Existing siblings are only replaced with --force
+        std::fs::write(&encrypted, b"existing").unwrap();
+        let refused = std::panic::catch_unwind(sibling).unwrap_err();
+        assert_eq!(
+            *refused.downcast::<String>().unwrap(),
+            format!(
+                "{} already exists, use --force to overwrite it",
+                encrypted.display()
+            )
+        );
+        assert_eq!(
+            output_path(&file, false, true, encrypted.clone()),
+            encrypted
+        );
+
+        assert_eq!(output_path(&file, true, false, encrypted.clone()), file);
+        let stdin = Path::new("-");
+        assert_eq!(
+            output_path(stdin, false, false, encrypted_path(stdin)),
+            stdin
+        );
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_in_place() {
+        let dir = temp_dir("in-place");
+        let params = ParamSetId::Ees1171Ep1.params();
+        let (private_key, public_key) =
+            (fixture("key/private.txt"), fixture("key/public.txt"));
+        let file = dir.join("file.txt");
+        std::fs::write(&file, b"in place").unwrap();
+
+        let output = output_path(&file, true, false, encrypted_path(&file));
+        let public = &mut open(&public_key, "no public key");
+        encrypt_file(&file, &output, public, params, false, None, false);
+        assert_eq!(
+            std::fs::read(&file).unwrap().len(),
+            params.enc_len() as usize
+        );
+
+        let output = output_path(&file, true, false, decrypted_path(&file));
+        decrypt_file(&file, &output, &private_key, &public_key, params, false);
+        assert_eq!(std::fs::read(&file).unwrap(), b"in place");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn failure_rate_counts_trials() {
+        assert_eq!(count_failures(5, ParamSetId::Ees401Ep1.params()), 0);
+        let none = std::panic::catch_unwind(|| {
+            count_failures(0, ParamSetId::Ees401Ep1.params())
+        });
+        assert!(none.is_err());
+
+        let (low, high) = confidence_interval(0, 5);
+        assert_eq!(low, 0.0);
+        assert!((high - 0.434).abs() < 1e-3, "{}", high);
+        let (low, high) = confidence_interval(50, 100);
+        assert!(low < 0.5 && 0.5 < high && (low + high - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn version_info_serializes_build_details() {
+        let info = version_info();
+        assert_eq!(info.crate_version, env!("CARGO_PKG_VERSION"));
+        assert!(info.backend.starts_with("libntru "));
+
+        let json = serde_json::to_value(&info).unwrap();
+        let keys = json.as_object().unwrap().keys().collect::<Vec<_>>();
+        assert_eq!(keys, ["backend", "crate_version", "features"]);
+        assert_eq!(json["crate_version"], env!("CARGO_PKG_VERSION"));
+        assert!(json["features"].is_array());
+    }
+
+    #[test]
+    fn partial_outputs_are_undone() {
+        let dir = temp_dir("partial");
+        let tracked = |path: &Path| {
+            partial_outputs()
+                .iter()
+                .any(|partial| partial.path() == path)
+        };
+
+        // Files existing before are never tracked, so never removed
+        let existing = dir.join("existing");
+        std::fs::write(&existing, b"kept").unwrap();
+        assert!(create_output(&existing, create_private).is_err());
+        assert!(!tracked(&existing));
+
+        let created = dir.join("created");
+        create_output(&created, create_private).unwrap();
+        assert!(tracked(&created));
+        Partial::Remove(created.clone()).undo();
+        complete_output(&created);
+        assert!(!tracked(&created) && !created.exists());
+
+        let mut file = OpenOptions::new().append(true).open(&existing).unwrap();
+        let partial = Partial::Truncate {
+            path: existing.clone(),
+            file: file.try_clone().unwrap(),
+            len: 4,
+        };
+        file.write_all(b" half").unwrap();
+        partial.undo();
+        assert_eq!(std::fs::read(&existing).unwrap(), b"kept");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn write_fd_checks_descriptors() {
+        use std::os::fd::AsRawFd;
+
+        let path = temp_dir("fd").join("output");
+        let mut file = File::create(&path).unwrap();
+        write_fd(file.as_raw_fd(), b"written").unwrap();
+
+        // The inherited descriptor stays open
+        file.write_all(b" twice").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"written twice");
+
+        let read_only = File::open(&path).unwrap();
+        assert!(write_fd(read_only.as_raw_fd(), b"").is_err());
+        // Closing a descriptor here would race with other tests opening
+        // files, and none can be that high
+        assert!(write_fd(i32::MAX, b"").is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn decrypted_files_are_no_more_readable_than_ciphertexts() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = temp_dir("permissions");
+        let params = ParamSetId::Ees1171Ep1.params();
+        let decrypt_into = |file: &Path, output: &Path| {
+            decrypt_file(
+                file,
+                output,
+                &fixture("key/private.txt"),
+                &fixture("key/public.txt"),
+                params,
+                true,
+            )
+        };
+
+        let file = dir.join("secret.enc");
+        std::fs::copy(fixture("pinned.enc.txt"), &file).unwrap();
+        let private = std::fs::Permissions::from_mode(0o600);
+        std::fs::set_permissions(&file, private).unwrap();
+
+        // New files take the permissions of the ciphertext
+        let output = dir.join("secret");
+        decrypt_into(&file, &output);
+        assert_eq!(std::fs::read(&output).unwrap(), b"pinned");
+        assert_eq!(mode(&output), 0o600);
+
+        // Replaced files keep theirs
+        let shared = std::fs::Permissions::from_mode(0o640);
+        std::fs::set_permissions(&output, shared).unwrap();
+        decrypt_into(&file, &output);
+        assert_eq!(mode(&output), 0o640);
+    }
+
+    #[test]
+    fn short_plaintexts_round_trip_with_every_parameter_set() {
+        for id in ParamSetId::ALL {
+            let params = id.params();
+            assert_eq!(params::min_plaintext_len(params), 0);
+
+            let key_pair = ntru::generate_key_pair(params, &get_rng()).unwrap();
+            for len in 0..=2 {
+                let plaintext = vec![0xa5; len];
+                let mut ciphertext = Vec::new();
+                encrypt(
+                    &mut &plaintext[..],
+                    &mut ciphertext,
+                    key_pair.get_public(),
+                    params,
+                    None,
+                    false,
+                )
+                .unwrap();
+
+                let mut decrypted = Vec::new();
+                decrypt(
+                    &mut &ciphertext[..],
+                    &mut decrypted,
+                    &key_pair,
+                    params,
+                )
+                .unwrap();
+                assert_eq!(decrypted, plaintext, "{} bytes with {}", len, id);
+            }
+        }
+    }
+
+    #[test]
+    fn private_key_weights_check_indices() {
+        for id in [ParamSetId::Ees401Ep1, ParamSetId::Ees401Ep2] {
+            let params = id.params();
+            let key_pair = ntru::generate_key_pair(params, &get_rng()).unwrap();
+            let private_key = key_pair.get_private().export(params);
+            assert_eq!(
+                private_key_weights(&private_key),
+                Some(dump::private_weights(params))
+            );
+        }
+
+        let key = std::fs::read_to_string(fixture("key/private.txt")).unwrap();
+        let private_key = encoding::decode(key.trim()).unwrap();
+        assert_eq!(private_key_weights(&private_key), Some(vec![106]));
+
+        // The first index, after N, q, the flags & the weights, is 2047
+        let mut corrupted = private_key.clone();
+        corrupted[9..11].copy_from_slice(&[0xff, 0xff]);
+        assert_eq!(private_key_weights(&corrupted), None);
+
+        // The first 2 indices are 0
+        let mut corrupted = private_key.clone();
+        corrupted[9..12].copy_from_slice(&[0, 0, 0]);
+        assert_eq!(private_key_weights(&corrupted), None);
+
+        // More ones than negative ones
+        let mut corrupted = private_key.clone();
+        corrupted[6] += 1;
+        assert_eq!(private_key_weights(&corrupted), None);
+
+        assert_eq!(private_key_weights(&private_key[..100]), None);
+    }
+
+    #[test]
+    fn corrupted_private_keys_are_refused() {
+        let params = ParamSetId::Ees1171Ep1.params();
+        let key = std::fs::read_to_string(fixture("key/private.txt")).unwrap();
+        let private_key = encoding::decode(key.trim()).unwrap();
+
+        // Derive a public key as `gen --from-private` would, catching errors
+        let derive = |private_key: &[u8]| {
+            let key = encoding::encode(private_key);
+            std::panic::catch_unwind(|| {
+                generate_key_pair_from_private_key(
+                    &mut key.as_bytes(),
+                    &mut Vec::new(),
+                    params,
+                )
+                .unwrap()
+            })
+            .map_err(|e| match e.downcast::<String>() {
+                Ok(message) => *message,
+                Err(e) => e.downcast_ref::<&str>().unwrap().to_string(),
+            })
+        };
+        assert_eq!(derive(&private_key), Ok(()));
+
+        // q of 4096 instead of 2048
+        let mut corrupted = private_key.clone();
+        corrupted[2..4].copy_from_slice(&4096u16.to_be_bytes());
+        assert_eq!(
+            derive(&corrupted).unwrap_err(),
+            "invalid private key: not generated with EES1171EP1"
+        );
+
+        // N of 1170, which no parameter set uses
+        let mut corrupted = private_key.clone();
+        corrupted[0..2].copy_from_slice(&1170u16.to_be_bytes());
+        assert_eq!(
+            derive(&corrupted).unwrap_err(),
+            "invalid private key: UnknownParamSet"
+        );
+
+        // Product-form flag set on a single polynomial
+        let mut corrupted = private_key.clone();
+        corrupted[4] |= 4;
+        assert_eq!(
+            derive(&corrupted).unwrap_err(),
+            "invalid private key: unexpected polynomial weights or indices"
+        );
+
+        // Truncated indices
+        let truncated = &private_key[..private_key.len() - 10];
+        assert_eq!(derive(truncated).unwrap_err(), "invalid private key size");
+    }
+
+    #[test]
+    fn config_file_provides_defaults() {
+        let dir = temp_dir("config");
+        let path = dir.join("ntru.toml");
+        std::fs::write(
+            &path,
+            "params = \"EES401EP1\"\nencoding = \"z85\"\narmor = true\n",
+        )
+        .unwrap();
+        let config = config::load(path);
+
+        let mut opt = Opt::from_iter(["ntru", "enc", "file", "key.pub"]);
+        apply_config(&mut opt, &config);
+        assert_eq!(opt.params, Some(ParamSetId::Ees401Ep1));
+        assert_eq!(opt.encoding, Some(Encoding::Z85));
+        assert!(matches!(opt.cmd, Command::Enc { armor: true, .. }));
+
+        // Explicit arguments take precedence
+        let mut opt = Opt::from_iter([
+            "ntru",
+            "--params",
+            "EES743EP1",
+            "--encoding",
+            "base64",
+            "enc",
+            "file",
+            "key.pub",
+        ]);
+        apply_config(&mut opt, &config);
+        assert_eq!(opt.params, Some(ParamSetId::Ees743Ep1));
+        assert_eq!(opt.encoding, Some(Encoding::Base64));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid config file")]
+    fn config_file_rejects_unknown_parameter_sets() {
+        let dir = temp_dir("config-unknown");
+        let path = dir.join("ntru.json");
+        std::fs::write(&path, r#"{ "params": "EES1EP1" }"#).unwrap();
+        config::load(path);
     }
 }
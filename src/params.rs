@@ -0,0 +1,300 @@
+use std::fmt;
+
+use ntru::encparams::{EncParams, ALL_PARAM_SETS};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Every parameter set shipped by the backend
+static PARAM_SETS: [EncParams; 18] = ALL_PARAM_SETS;
+
+/// Names of the NIST PQC submission, with or without a `ntru-` prefix, mapped
+/// to the parameter set recommended by libntru for the same security level
+const ALIASES: [(&str, ParamSetId); 4] = [
+    ("hps2048509", ParamSetId::Ees613Ep1),
+    ("hps2048677", ParamSetId::Ees887Ep1),
+    ("hrss701", ParamSetId::Ees887Ep1),
+    ("hps4096821", ParamSetId::Ees1171Ep1),
+];
+
+/// Identifier of a parameter set shipped by the backend
+///
+/// `EncParams` can't be cloned, so store this `Copy` handle wherever a
+/// parameter set must be owned and call `params()` to use it. It is
+/// (de)serialized as the name of the parameter set so that it stays
+/// valid across versions, unlike the parameters themselves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParamSetId {
+    Ees401Ep1,
+    Ees449Ep1,
+    Ees677Ep1,
+    Ees1087Ep2,
+    Ees541Ep1,
+    Ees613Ep1,
+    Ees887Ep1,
+    Ees1171Ep1,
+    Ees659Ep1,
+    Ees761Ep1,
+    Ees1087Ep1,
+    Ees1499Ep1,
+    Ees401Ep2,
+    Ees439Ep1,
+    Ees443Ep1,
+    Ees593Ep1,
+    Ees587Ep1,
+    Ees743Ep1,
+}
+
+impl ParamSetId {
+    /// Every identifier, in the same order as `ALL_PARAM_SETS`
+    pub const ALL: [ParamSetId; 18] = [
+        ParamSetId::Ees401Ep1,
+        ParamSetId::Ees449Ep1,
+        ParamSetId::Ees677Ep1,
+        ParamSetId::Ees1087Ep2,
+        ParamSetId::Ees541Ep1,
+        ParamSetId::Ees613Ep1,
+        ParamSetId::Ees887Ep1,
+        ParamSetId::Ees1171Ep1,
+        ParamSetId::Ees659Ep1,
+        ParamSetId::Ees761Ep1,
+        ParamSetId::Ees1087Ep1,
+        ParamSetId::Ees1499Ep1,
+        ParamSetId::Ees401Ep2,
+        ParamSetId::Ees439Ep1,
+        ParamSetId::Ees443Ep1,
+        ParamSetId::Ees593Ep1,
+        ParamSetId::Ees587Ep1,
+        ParamSetId::Ees743Ep1,
+    ];
+    /// Parameter set used when none has been selected
+    pub const DEFAULT: ParamSetId = ParamSetId::Ees1171Ep1;
+
+    /// Find a parameter set by its (case-insensitive) name, e.g. `EES1171EP1`,
+    /// or by the name of the closest NIST PQC one, e.g. `ntru-hps2048509`
+    pub fn from_name(maybe_name: &str) -> Option<ParamSetId> {
+        let maybe_name = maybe_name.trim().to_ascii_lowercase();
+        let alias = maybe_name.strip_prefix("ntru-").unwrap_or(&maybe_name);
+
+        PARAM_SETS
+            .iter()
+            .position(|params| name(params).eq_ignore_ascii_case(&maybe_name))
+            .map(|i| ParamSetId::ALL[i])
+            .or_else(|| {
+                ALIASES
+                    .iter()
+                    .find(|(name, _)| *name == alias)
+                    .map(|&(_, id)| id)
+            })
+    }
+
+    /// Get every identifier ordered by security level, then by public key
+    /// length, e.g. to present them from the weakest to the strongest
+    pub fn by_security() -> Vec<ParamSetId> {
+        let mut ids = ParamSetId::ALL.to_vec();
+        ids.sort_by_key(|id| (id.security_bits(), id.params().public_len()));
+        ids
+    }
+
+    /// Get the parameter set
+    pub fn params(self) -> &'static EncParams { &PARAM_SETS[self as usize] }
+
+    /// Get the name of the parameter set
+    pub fn name(self) -> String { name(self.params()) }
+
+    /// Get the security level of the parameter set in bits, as documented by
+    /// libntru
+    pub fn security_bits(self) -> u16 {
+        match self {
+            ParamSetId::Ees401Ep1
+            | ParamSetId::Ees541Ep1
+            | ParamSetId::Ees659Ep1
+            | ParamSetId::Ees401Ep2 => 112,
+            ParamSetId::Ees449Ep1
+            | ParamSetId::Ees613Ep1
+            | ParamSetId::Ees761Ep1
+            | ParamSetId::Ees439Ep1
+            | ParamSetId::Ees443Ep1 => 128,
+            ParamSetId::Ees677Ep1
+            | ParamSetId::Ees887Ep1
+            | ParamSetId::Ees1087Ep1
+            | ParamSetId::Ees593Ep1
+            | ParamSetId::Ees587Ep1 => 192,
+            ParamSetId::Ees1087Ep2
+            | ParamSetId::Ees1171Ep1
+            | ParamSetId::Ees1499Ep1
+            | ParamSetId::Ees743Ep1 => 256,
+        }
+    }
+}
+
+impl fmt::Display for ParamSetId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.name())
+    }
+}
+
+impl TryFrom<&str> for ParamSetId {
+    type Error = String;
+
+    fn try_from(maybe_name: &str) -> Result<Self, Self::Error> {
+        ParamSetId::from_name(maybe_name)
+            .ok_or_else(|| format!("unknown parameter set: {}", maybe_name))
+    }
+}
+
+impl Serialize for ParamSetId {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for ParamSetId {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        let maybe_name = String::deserialize(deserializer)?;
+        ParamSetId::try_from(maybe_name.as_str()).map_err(de::Error::custom)
+    }
+}
+
+/// Get the name of a parameter set without the trailing NUL padding
+pub fn name(params: &EncParams) -> String {
+    params.get_name().trim_end_matches('\0').to_owned()
+}
+
+/// Summarize a parameter set on one line, e.g. `EES1171EP1 (256-bit, N=1171,
+/// q=2048, pub=1615B, maxmsg=186B)`, as `EncParams` can't implement `Display`
+/// here
+pub fn profile(params: &EncParams) -> String {
+    let name = name(params);
+    let bits =
+        ParamSetId::from_name(&name).map_or(0, ParamSetId::security_bits);
+
+    format!(
+        "{} ({}-bit, N={}, q={}, pub={}B, maxmsg={}B)",
+        name,
+        bits,
+        params.get_n(),
+        params.get_q(),
+        params.public_len(),
+        params.max_msg_len()
+    )
+}
+
+/// Get the minimum length of a plaintext, 0 for every parameter set as libntru
+/// pads each message & stores its length, so even an empty one can be encrypted
+pub fn min_plaintext_len(_params: &EncParams) -> u64 { 0 }
+
+/// Find every parameter set whose public keys are `len` bytes long
+pub fn from_public_key_len(len: usize) -> Vec<&'static EncParams> {
+    PARAM_SETS
+        .iter()
+        .filter(|params| params.public_len() as usize == len)
+        .collect()
+}
+
+/// Find every parameter set of which `len` bytes could be whole ciphertexts
+pub fn from_ciphertext_len(len: usize) -> Vec<&'static EncParams> {
+    PARAM_SETS
+        .iter()
+        .filter(|params| {
+            len > 0 && len.is_multiple_of(params.enc_len() as usize)
+        })
+        .collect()
+}
+
+/// Check that every parameter set shipped by the backend is consistent, to
+/// catch a broken build early
+pub fn self_check() -> Result<(), String> {
+    for id in ParamSetId::ALL {
+        let (name, params) = (id.name(), id.params());
+        if ParamSetId::from_name(&name) != Some(id) {
+            return Err(format!("{} doesn't resolve to itself", name));
+        }
+
+        // The message, its length & the random padding are encoded in the N
+        // ternary coefficients of a polynomial, 1.5 bits each
+        let message_bits =
+            (params.max_msg_len() as u32 + 1) * 8 + params.get_db() as u32;
+        if params.max_msg_len() == 0
+            || message_bits > params.get_n() as u32 * 3 / 2
+        {
+            return Err(format!("{} has an invalid plaintext length", name));
+        }
+
+        // Both are a polynomial mod q, after `N` & `q` for public keys
+        if params.enc_len() == 0 || params.public_len() != params.enc_len() + 4
+        {
+            return Err(format!("{} has invalid key lengths", name));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn try_from_names() {
+        assert_eq!(
+            ParamSetId::try_from("EES743EP1"),
+            Ok(ParamSetId::Ees743Ep1)
+        );
+        assert_eq!(
+            ParamSetId::try_from(" ees401ep2 "),
+            Ok(ParamSetId::Ees401Ep2)
+        );
+        assert_eq!(
+            ParamSetId::try_from("ntru-hps2048509"),
+            Ok(ParamSetId::Ees613Ep1)
+        );
+        assert_eq!(
+            ParamSetId::try_from("EES1EP1"),
+            Err("unknown parameter set: EES1EP1".to_owned())
+        );
+    }
+
+    #[test]
+    fn shipped_parameter_sets_pass_self_check() {
+        assert_eq!(self_check(), Ok(()))
+    }
+
+    #[test]
+    fn by_security_orders_every_set() {
+        let ids = ParamSetId::by_security();
+        assert_eq!(ids.len(), ParamSetId::ALL.len());
+        assert!(ParamSetId::ALL.iter().all(|id| ids.contains(id)));
+
+        let key =
+            |id: &ParamSetId| (id.security_bits(), id.params().public_len());
+        assert!(ids.windows(2).all(|pair| key(&pair[0]) <= key(&pair[1])));
+        assert_eq!(ids[0].security_bits(), 112);
+        assert_eq!(ids[ids.len() - 1].security_bits(), 256);
+    }
+
+    #[test]
+    fn from_public_key_len_finds_every_set() {
+        for id in ParamSetId::ALL {
+            let len = id.params().public_len() as usize;
+            assert!(from_public_key_len(len).contains(&id.params()));
+        }
+        assert!(from_public_key_len(0).is_empty());
+    }
+
+    #[test]
+    fn from_ciphertext_len_finds_whole_records() {
+        for params in &PARAM_SETS {
+            let len = params.enc_len() as usize;
+            assert!(from_ciphertext_len(len).contains(&params));
+            assert!(from_ciphertext_len(3 * len).contains(&params));
+            assert!(!from_ciphertext_len(len + 1).contains(&params));
+        }
+
+        assert!(from_ciphertext_len(0).is_empty());
+        assert!(from_ciphertext_len(7).is_empty());
+    }
+}
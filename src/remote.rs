@@ -0,0 +1,86 @@
+use std::{io::Read, path::Path};
+
+/// Maximum time to connect to the server of an URL
+#[cfg(feature = "http")]
+const CONNECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Maximum time to wait for each read of a response
+#[cfg(feature = "http")]
+const READ_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Tell if a file argument is an `http://` or `https://` URL
+pub fn is_url(path: &Path) -> bool {
+    path.to_str().is_some_and(|path| {
+        path.starts_with("http://") || path.starts_with("https://")
+    })
+}
+
+/// Fetch a key or ciphertext file from an URL, panicking with `msg` on failure
+#[cfg(feature = "http")]
+pub fn fetch(url: &Path, msg: &str) -> Box<dyn Read> {
+    let url = url.to_str().expect("invalid url");
+    let response = ureq::AgentBuilder::new()
+        .timeout_connect(CONNECT_TIMEOUT)
+        .timeout_read(READ_TIMEOUT)
+        .build()
+        .get(url)
+        .call()
+        .unwrap_or_else(|e| panic!("{}: {}", msg, e));
+
+    Box::new(response.into_reader())
+}
+
+/// Fetching without the `http` feature always fails
+#[cfg(not(feature = "http"))]
+pub fn fetch(_url: &Path, _msg: &str) -> Box<dyn Read> {
+    panic!("reading files from URLs requires the `http` feature")
+}
+
+#[cfg(all(test, feature = "http"))]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        path::PathBuf,
+    };
+
+    use super::*;
+
+    /// Serve `body` to a single HTTP request on a local port, returning its URL
+    fn serve(body: &'static [u8]) -> PathBuf {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/key.pub", listener.local_addr().unwrap());
+
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut request = BufReader::new(&stream);
+            let mut line = String::new();
+            while request.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+
+            write!(
+                &stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n",
+                body.len()
+            )
+            .unwrap();
+            (&stream).write_all(body).unwrap();
+        });
+
+        PathBuf::from(url)
+    }
+
+    #[test]
+    fn fetch_from_local_server() {
+        let public_key = include_bytes!("../test/key/public.txt");
+        let url = serve(public_key);
+        assert!(is_url(&url));
+
+        let mut content = Vec::new();
+        fetch(&url, "can't fetch key")
+            .read_to_end(&mut content)
+            .unwrap();
+        assert_eq!(content, public_key);
+    }
+}
@@ -0,0 +1,46 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// Whether durations are being recorded (`--time`)
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Total duration spent in each phase, in order of first use
+static PHASES: Mutex<Vec<(&str, Duration)>> = Mutex::new(Vec::new());
+
+/// Start recording durations
+pub fn enable() { ENABLED.store(true, Ordering::Relaxed); }
+
+/// Run `f`, adding the time it took to the total of `phase`
+pub fn measure<T>(phase: &'static str, f: impl FnOnce() -> T) -> T {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return f();
+    }
+
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+
+    let mut phases = PHASES.lock().unwrap();
+    match phases.iter_mut().find(|(name, _)| *name == phase) {
+        Some((_, total)) => *total += elapsed,
+        None => phases.push((phase, elapsed)),
+    }
+
+    result
+}
+
+/// Print the recorded durations to stderr
+pub fn report() {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+
+    for (phase, total) in PHASES.lock().unwrap().iter() {
+        eprintln!("{:>6} :: {:?}", phase, total);
+    }
+}
@@ -0,0 +1,141 @@
+use std::{
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+/// Run the `ntru` binary with `args`
+fn ntru(args: &[&str]) -> Output { ntru_with_stdin(args, b"") }
+
+/// Run the `ntru` binary with `args`, writing `stdin` to its standard input
+fn ntru_with_stdin(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ntru"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to run ntru");
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().expect("failed to run ntru")
+}
+
+/// Get the path of a test file
+fn fixture(name: &str) -> String {
+    format!("{}/test/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+#[test]
+fn invalid_command_lines_are_usage_errors() {
+    let cases: [&[&str]; 4] = [
+        &["--bogus"],
+        &["enc"],
+        &["--params", "EES1EP1", "enc", "file.txt"],
+        &["--encoding", "base32", "gen"],
+    ];
+    for args in cases {
+        let output = ntru(args);
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+
+        let output = ntru(&[&["--json-errors"], args].concat());
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        let error = serde_json::from_slice::<serde_json::Value>(&output.stderr)
+            .unwrap();
+        assert_eq!(error["code"], 2);
+        assert_eq!(error["kind"], "usage");
+    }
+
+    assert_eq!(ntru(&["--help"]).status.code(), Some(0));
+}
+
+#[test]
+fn params_json_lists_every_set() {
+    let output = ntru(&["params", "--json"]);
+    assert!(output.status.success());
+
+    let sets =
+        serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap();
+    let sets = sets.as_array().unwrap();
+    assert_eq!(sets.len(), 18);
+    for set in sets {
+        let mut keys = set.as_object().unwrap().keys().collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(
+            keys,
+            [
+                "ciphertext_len",
+                "default",
+                "max_plaintext_len",
+                "name",
+                "oid",
+                "private_key_len",
+                "public_key_len",
+                "security_bits",
+            ]
+        );
+    }
+
+    let default = sets.iter().find(|set| set["default"] == true).unwrap();
+    assert_eq!(default["name"], "EES1171EP1");
+    assert_eq!(default["security_bits"], 256);
+    assert!(sets.iter().any(|set| set["name"] == "EES401EP1"));
+}
+
+#[test]
+fn stable_info_of_the_default_set() {
+    let output = ntru(&["info", "--stable"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "name=EES1171EP1
+backend=libntru 0.5 (https://github.com/tbuktu/libntru)
+public_key_len=1615
+private_key_len=301
+ciphertext_len=1611
+max_plaintext_len=186
+db=256
+n=1171
+p=3
+q=2048
+oid=000604
+"
+    );
+}
+
+#[test]
+fn time_reports_crypto() {
+    let public_key = fixture("key/public.txt");
+    let output =
+        ntru_with_stdin(&["--time", "enc", "-", &public_key], b"timed");
+    assert!(output.status.success());
+    assert_eq!(output.stdout.len(), 1611);
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr
+            .lines()
+            .any(|line| line.trim_start().starts_with("crypto :: ")),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn verbosity_of_gen() {
+    let output = ntru(&["-v", "--params", "EES401EP1", "gen"]);
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let fingerprint = stderr
+        .lines()
+        .find_map(|line| line.strip_prefix("public key sha256 fingerprint: "));
+    let fingerprint = fingerprint.unwrap_or_else(|| panic!("{}", stderr));
+    assert_eq!(fingerprint.len(), 64);
+    assert!(fingerprint.bytes().all(|c| c.is_ascii_hexdigit()));
+
+    // Only the bare keys when quiet
+    let output = ntru(&["-q", "--params", "EES401EP1", "gen"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 2);
+    assert!(!stdout.contains("---"));
+    assert!(output.stderr.is_empty());
+}